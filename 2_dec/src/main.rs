@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, Read};
 
@@ -54,7 +55,7 @@ fn get_reports() -> Result<Vec<Report>, io::Error> {
     Ok(reports)
 }
 
-fn find_general_direction(level_steps: &Vec<LevelStep>) -> Direction {
+fn find_general_direction(level_steps: &[LevelStep]) -> Direction {
     let mut direction_count_map: HashMap<Direction, usize> = HashMap::new();
     for s in level_steps.iter() {
         let direction = s.direction();
@@ -65,56 +66,164 @@ fn find_general_direction(level_steps: &Vec<LevelStep>) -> Direction {
     *direction_count_map.iter().max_by_key(|x| x.1).unwrap().0
 }
 
-fn validate_report(report: &Report) -> bool {
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum UnsafeReason { StepTooLarge, ZeroStep, DirectionFlip }
+
+impl UnsafeReason {
+    fn classify(step: LevelStep, main_direction: Direction) -> UnsafeReason {
+        match step.direction() {
+            Direction::None => UnsafeReason::ZeroStep,
+            direction if direction != main_direction => UnsafeReason::DirectionFlip,
+            _ => UnsafeReason::StepTooLarge,
+        }
+    }
+}
+
+impl fmt::Display for UnsafeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsafeReason::StepTooLarge => write!(f, "step too large"),
+            UnsafeReason::ZeroStep => write!(f, "zero step"),
+            UnsafeReason::DirectionFlip => write!(f, "direction flip"),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+struct InvalidStep {
+    index: usize,
+    reason: UnsafeReason,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+enum ReportVerdict {
+    Safe,
+    Dampened { offence: InvalidStep, removed_level: usize },
+    Unsafe { offence: InvalidStep, attempted_removals: [usize; 2] },
+}
+
+impl ReportVerdict {
+    fn is_safe(&self) -> bool {
+        !matches!(self, ReportVerdict::Unsafe { .. })
+    }
+}
+
+fn validate_report(report: &Report) -> ReportVerdict {
     let maybe_invalid_step = try_find_invalid_step(report);
 
     match maybe_invalid_step {
-        Some(invalid_step_index) => validate_with_problem_dampening(report, invalid_step_index),
-        None => true
+        Some(invalid_step) => validate_with_problem_dampening(report, invalid_step),
+        None => ReportVerdict::Safe
     }
 }
 
-fn try_find_invalid_step(report: &Vec<i32>) -> Option<usize> {
-    let level_steps: Vec<i32> = report.windows(2)
+fn try_find_invalid_step(report: &[Level]) -> Option<InvalidStep> {
+    let level_steps: Vec<LevelStep> = report.windows(2)
         .map(|window| window[0] - window[1])
         .collect();
 
     let main_direction = find_general_direction(&level_steps);
     
-    let maybe_invalid_step = level_steps
+    level_steps
         .iter()
-        .position(|x| !x.validate(main_direction));
-    
-    maybe_invalid_step
+        .position(|x| !x.validate(main_direction))
+        .map(|index| InvalidStep { index, reason: UnsafeReason::classify(level_steps[index], main_direction) })
 }
 
-fn validate_with_problem_dampening(report: &Report, index: usize) -> bool {
-    let mut dampened_report = report.clone();
-    dampened_report.remove(index);
-    
-    match try_find_invalid_step(&dampened_report) {
-        None => true,
-        Some(_) => {
-            let mut dampened_report = report.clone();
-            dampened_report.remove(index + 1);
-            match try_find_invalid_step(&dampened_report){
-                None => true,
-                Some(_) => false
-            }
-        },
+fn validate_with_problem_dampening(report: &Report, offence: InvalidStep) -> ReportVerdict {
+    let attempted_removals = [offence.index, offence.index + 1];
+
+    for removed_level in attempted_removals {
+        let mut dampened_report = report.clone();
+        dampened_report.remove(removed_level);
+
+        if try_find_invalid_step(&dampened_report).is_none() {
+            return ReportVerdict::Dampened { offence, removed_level };
+        }
     }
+
+    ReportVerdict::Unsafe { offence, attempted_removals }
+}
+
+fn highlight_step(report: &[Level], step_index: usize) -> String {
+    report.iter()
+        .enumerate()
+        .map(|(i, level)| match i {
+            i if i == step_index || i == step_index + 1 => format!("[{}]", level),
+            _ => level.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 fn main() -> io::Result<()> {
     let reports = get_reports()?;
+    let diagnose = std::env::args().any(|arg| arg == "--diagnose");
+
+    let verdicts: Vec<ReportVerdict> = reports.iter()
+        .map(validate_report)
+        .collect();
 
-    let valid_reports  = 
-        reports.iter()
-            .map(validate_report)
-            .filter(|x| *x)
-            .count();
+    if diagnose {
+        for (i, (report, verdict)) in reports.iter().zip(verdicts.iter()).enumerate() {
+            if let ReportVerdict::Unsafe { offence, attempted_removals } = verdict {
+                println!(
+                    "Report {:>4}: {} -> {} at step {} (dampener tried removing levels {} and {})",
+                    i + 1,
+                    highlight_step(report, offence.index),
+                    offence.reason,
+                    offence.index,
+                    attempted_removals[0],
+                    attempted_removals[1],
+                );
+            }
+        }
+    }
+
+    let valid_reports = verdicts.iter()
+        .filter(|verdict| verdict.is_safe())
+        .count();
     
     println!("Valid Reports {}", valid_reports);
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdicts_example() {
+        let reports: Vec<Report> = vec![
+            vec![7, 6, 4, 2, 1],
+            vec![1, 2, 7, 8, 9],
+            vec![9, 7, 6, 2, 1],
+            vec![1, 3, 2, 4, 5],
+            vec![8, 6, 4, 4, 1],
+            vec![1, 3, 6, 7, 9],
+        ];
+
+        let verdicts: Vec<ReportVerdict> = reports.iter().map(validate_report).collect();
+
+        assert_eq!(verdicts[0], ReportVerdict::Safe);
+        assert_eq!(verdicts[1], ReportVerdict::Unsafe {
+            offence: InvalidStep { index: 1, reason: UnsafeReason::StepTooLarge },
+            attempted_removals: [1, 2],
+        });
+        assert_eq!(verdicts[3], ReportVerdict::Dampened {
+            offence: InvalidStep { index: 1, reason: UnsafeReason::DirectionFlip },
+            removed_level: 1,
+        });
+        assert_eq!(verdicts[4], ReportVerdict::Dampened {
+            offence: InvalidStep { index: 2, reason: UnsafeReason::ZeroStep },
+            removed_level: 2,
+        });
+        assert_eq!(verdicts.iter().filter(|v| v.is_safe()).count(), 4);
+    }
+
+    #[test]
+    fn highlight_offending_levels() {
+        assert_eq!(highlight_step(&[1, 2, 7, 8, 9], 1), "1 [2] [7] 8 9");
+    }
+}
@@ -1,6 +1,17 @@
+mod memory;
+
 use std::fs::File;
 use std::io::{Read, Result};
-use regex::Regex;
+
+use memory::explain::{audit, explain, render};
+use memory::lexer::Lexer;
+use memory::interpreter::{Interpreter, Mode};
+use memory::registry::InstructionSet;
+
+#[cfg(not(feature = "bigint"))]
+type Sum = u64;
+#[cfg(feature = "bigint")]
+type Sum = num_bigint::BigUint;
 
 
 
@@ -14,20 +25,33 @@ fn read_file_contents(filename: &str) -> Result<String> {
 
 fn main() {
     let contents = read_file_contents("input.txt").unwrap();
-    let re = Regex::new(r"(?ms)(?:\A|do\(\))(?:.*?)(?:don't\(\)|\z)").unwrap();
-
-    let sum: f64 = re.captures_iter(&contents)
-        .map(|cap| {
-            let re = Regex::new(r"(?ms)mul\((?P<left>[0-9]+),(?P<right>[0-9]+)\)").unwrap();    
-            let inner_cap = cap.get(0).unwrap().as_str();
-            re.captures_iter(inner_cap)
-                .map(|cap| {
-                    let left = cap["left"].parse::<f64>().unwrap();
-                    let right = cap["right"].parse::<f64>().unwrap();
-                    left * right
-                })
-                .sum::<f64>()      
-        }).sum();
-
-    println!("Sum: {}", sum);
+    let instructions = if std::env::args().any(|arg| arg == "--extended") {
+        InstructionSet::extended()
+    } else {
+        InstructionSet::standard()
+    };
+
+    if std::env::args().any(|arg| arg == "--explain") {
+        match explain::<Sum>(&contents, &instructions, Mode::HonourConditionals) {
+            Ok(explanation) => {
+                println!("{}", render(&contents, &explanation));
+                println!("{}", audit(&contents, &instructions, &explanation));
+                println!("Explained sum: {}", explanation.sum());
+            }
+            Err(err) => println!("Explain failed: {}", err),
+        }
+        return;
+    }
+
+    let sum = Interpreter::<Sum>::new(&instructions, Mode::IgnoreConditionals).run(Lexer::new(&contents, &instructions));
+    match sum {
+        Ok(sum) => println!("Sum (part 1): {}", sum),
+        Err(err) => println!("Sum (part 1) failed: {}", err),
+    }
+
+    let sum = Interpreter::<Sum>::new(&instructions, Mode::HonourConditionals).run(Lexer::new(&contents, &instructions));
+    match sum {
+        Ok(sum) => println!("Sum: {}", sum),
+        Err(err) => println!("Sum failed: {}", err),
+    }
 }
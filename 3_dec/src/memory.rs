@@ -0,0 +1,27 @@
+pub mod lexer;
+pub mod interpreter;
+pub mod registry;
+pub mod arithmetic;
+pub mod explain;
+
+use registry::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub opcode: usize,
+    pub args: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub instruction: Instruction,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearMiss {
+    pub opcode: usize,
+    pub start: usize,
+    pub end: usize,
+}
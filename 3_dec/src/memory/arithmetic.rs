@@ -0,0 +1,48 @@
+use std::fmt;
+
+use super::registry::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArithmeticError {
+    Overflow { start: usize, end: usize },
+    OperandOverflow { start: usize, end: usize },
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArithmeticError::Overflow { start, end } => {
+                write!(f, "arithmetic overflow in instruction at bytes {}..{}", start, end)
+            }
+            ArithmeticError::OperandOverflow { start, end } => {
+                write!(f, "operand too large in instruction at bytes {}..{}", start, end)
+            }
+        }
+    }
+}
+
+pub trait Accumulator: Sized {
+    fn zero() -> Self;
+    fn checked_accumulate(&self, value: Value) -> Option<Self>;
+}
+
+impl Accumulator for u64 {
+    fn zero() -> u64 {
+        0
+    }
+
+    fn checked_accumulate(&self, value: Value) -> Option<u64> {
+        self.checked_add(value)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl Accumulator for num_bigint::BigUint {
+    fn zero() -> num_bigint::BigUint {
+        num_bigint::BigUint::ZERO
+    }
+
+    fn checked_accumulate(&self, value: Value) -> Option<num_bigint::BigUint> {
+        Some(self + value)
+    }
+}
@@ -0,0 +1,207 @@
+use super::Token;
+use super::arithmetic::{Accumulator, ArithmeticError};
+use super::interpreter::{Interpreter, Mode};
+use super::lexer::{Lexeme, Lexer};
+use super::registry::{InstructionSet, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    Enabled,
+    Disabled,
+    Counted { opcode: usize, contribution: Value },
+    Skipped { opcode: usize },
+    Conditional { opcode: usize },
+    NearMiss { opcode: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub kind: SpanKind,
+}
+
+// Regions (`Enabled`/`Disabled`) tile the whole input; instruction and
+// near-miss spans lie on top of them. Both lists are ordered by `start`.
+#[derive(Debug, PartialEq)]
+pub struct Explanation<A = u64> {
+    pub regions: Vec<Span>,
+    pub spans: Vec<Span>,
+    sum: A,
+}
+
+impl<A> Explanation<A> {
+    pub fn sum(&self) -> &A {
+        &self.sum
+    }
+}
+
+fn region(start: usize, end: usize, enabled: bool) -> Span {
+    let kind = if enabled { SpanKind::Enabled } else { SpanKind::Disabled };
+    Span { start, end, kind }
+}
+
+pub fn explain<A: Accumulator>(input: &str, instructions: &InstructionSet, mode: Mode) -> Result<Explanation<A>, ArithmeticError> {
+    let mut interpreter: Interpreter<A> = Interpreter::new(instructions, mode);
+    let mut regions = Vec::new();
+    let mut spans = Vec::new();
+    let mut region_start = 0;
+
+    for lexeme in Lexer::new(input, instructions).lexemes() {
+        let Token { instruction, start, end } = match lexeme {
+            Lexeme::Token(token) => token,
+            Lexeme::Error(err) => return Err(err),
+            Lexeme::NearMiss(near_miss) => {
+                let kind = SpanKind::NearMiss { opcode: near_miss.opcode };
+                spans.push(Span { start: near_miss.start, end: near_miss.end, kind });
+                continue;
+            }
+        };
+        let opcode = instruction.opcode;
+        let was_enabled = interpreter.enabled();
+        let kind = match interpreter.step(&Token { instruction, start, end })? {
+            Some(contribution) => SpanKind::Counted { opcode, contribution },
+            None if instructions.get(opcode).semantics.is_conditional() => SpanKind::Conditional { opcode },
+            None => SpanKind::Skipped { opcode },
+        };
+        spans.push(Span { start, end, kind });
+
+        if interpreter.enabled() != was_enabled {
+            if region_start < end {
+                regions.push(region(region_start, end, was_enabled));
+            }
+            region_start = end;
+        }
+    }
+    if region_start < input.len() {
+        regions.push(region(region_start, input.len(), interpreter.enabled()));
+    }
+
+    let sum = interpreter.run(std::iter::empty())?;
+    Ok(Explanation { regions, spans, sum })
+}
+
+const RESET: &str = "\x1b[0m";
+
+fn style(kind: SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Enabled => "",
+        SpanKind::Disabled => "\x1b[2m",
+        SpanKind::Counted { .. } => "\x1b[1;32m",
+        SpanKind::Skipped { .. } => "\x1b[9;36m",
+        SpanKind::Conditional { .. } => "\x1b[1;34m",
+        SpanKind::NearMiss { .. } => "\x1b[4;33m",
+    }
+}
+
+pub fn render<A>(input: &str, explanation: &Explanation<A>) -> String {
+    let mut styles = vec![SpanKind::Enabled; input.len()];
+    for span in explanation.regions.iter().chain(explanation.spans.iter()) {
+        styles[span.start..span.end].fill(span.kind);
+    }
+
+    let mut rendered = String::with_capacity(input.len() * 2);
+    let mut current = SpanKind::Enabled;
+    for (i, chr) in input.char_indices() {
+        if styles[i] != current {
+            current = styles[i];
+            rendered.push_str(RESET);
+            rendered.push_str(style(current));
+        }
+        rendered.push(chr);
+    }
+    rendered.push_str(RESET);
+    rendered
+}
+
+pub fn audit<A>(input: &str, instructions: &InstructionSet, explanation: &Explanation<A>) -> String {
+    explanation.spans.iter()
+        .map(|span| {
+            let text = &input[span.start..span.end];
+            let name = |opcode: usize| instructions.get(opcode).name.as_str();
+            let note = match span.kind {
+                SpanKind::Counted { contribution, .. } => format!("+{}", contribution),
+                SpanKind::Skipped { .. } => "disabled".to_string(),
+                SpanKind::Conditional { opcode } => format!("{} (conditional)", name(opcode)),
+                SpanKind::NearMiss { opcode } => format!("near miss for {}", name(opcode)),
+                SpanKind::Enabled | SpanKind::Disabled => String::new(),
+            };
+            format!("{:>6}..{:<6} {:<16} {}", span.start, span.end, text.escape_debug(), note)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static INPUT: &str = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn explain_example() {
+        let instructions = InstructionSet::standard();
+        let explanation: Explanation = explain(INPUT, &instructions, Mode::HonourConditionals).unwrap();
+
+        let regions: Vec<(usize, usize, SpanKind)> = explanation.regions.iter()
+            .map(|span| (span.start, span.end, span.kind))
+            .collect();
+        assert_eq!(regions, vec![
+            (0, 27, SpanKind::Enabled),
+            (27, 63, SpanKind::Disabled),
+            (63, INPUT.len(), SpanKind::Enabled),
+        ]);
+
+        let contributions: Vec<(&str, Value)> = explanation.spans.iter()
+            .filter_map(|span| match span.kind {
+                SpanKind::Counted { contribution, .. } => Some((&INPUT[span.start..span.end], contribution)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(contributions, vec![("mul(2,4)", 8), ("mul(8,5)", 40)]);
+        assert_eq!(*explanation.sum(), 48);
+        assert!(explanation.spans.iter().any(|span| matches!(span.kind, SpanKind::NearMiss { .. }) && span.start == 37));
+    }
+
+    #[test]
+    fn explain_ignoring_conditionals_has_single_region() {
+        let instructions = InstructionSet::standard();
+        let explanation: Explanation = explain(INPUT, &instructions, Mode::IgnoreConditionals).unwrap();
+
+        assert_eq!(explanation.regions, vec![Span { start: 0, end: INPUT.len(), kind: SpanKind::Enabled }]);
+        assert_eq!(*explanation.sum(), 161);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn explain_arbitrary_precision() {
+        use crate::memory::registry::{InstructionSpec, Semantics};
+
+        let instructions = InstructionSet::standard()
+            .register(InstructionSpec::new("max", 0, Semantics::Accumulate(|_| Some(u64::MAX))));
+        let input = "max()max()mul(2,1)";
+
+        assert!(explain::<u64>(input, &instructions, Mode::IgnoreConditionals).is_err());
+        let explanation: Explanation<num_bigint::BigUint> = explain(input, &instructions, Mode::IgnoreConditionals).unwrap();
+        assert_eq!(explanation.sum().to_string(), "36893488147419103232");
+    }
+
+    #[test]
+    fn render_keeps_text() {
+        let instructions = InstructionSet::standard();
+        let explanation: Explanation = explain(INPUT, &instructions, Mode::HonourConditionals).unwrap();
+        let rendered = render(INPUT, &explanation);
+
+        let mut stripped = String::new();
+        let mut in_escape = false;
+        for chr in rendered.chars() {
+            match chr {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if !in_escape => stripped.push(chr),
+                _ => {}
+            }
+        }
+        assert_eq!(stripped, INPUT);
+    }
+}
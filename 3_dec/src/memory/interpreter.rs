@@ -0,0 +1,116 @@
+use super::Token;
+use super::arithmetic::{Accumulator, ArithmeticError};
+use super::registry::{InstructionSet, Semantics, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    IgnoreConditionals,
+    HonourConditionals,
+}
+
+pub struct Interpreter<'a, A: Accumulator = u64> {
+    instructions: &'a InstructionSet,
+    mode: Mode,
+    enabled: bool,
+    sum: A,
+}
+
+impl<'a, A: Accumulator> Interpreter<'a, A> {
+    pub fn new(instructions: &'a InstructionSet, mode: Mode) -> Interpreter<'a, A> {
+        Interpreter { instructions, mode, enabled: true, sum: A::zero() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn step(&mut self, token: &Token) -> Result<Option<Value>, ArithmeticError> {
+        let conditional = self.mode == Mode::HonourConditionals;
+        let overflow = ArithmeticError::Overflow { start: token.start, end: token.end };
+        match self.instructions.get(token.instruction.opcode).semantics {
+            Semantics::Accumulate(apply) if self.enabled => {
+                let value = apply(&token.instruction.args).ok_or(overflow)?;
+                self.sum = self.sum.checked_accumulate(value).ok_or(overflow)?;
+                return Ok(Some(value));
+            }
+            Semantics::Accumulate(_) => {}
+            Semantics::Enable if conditional => self.enabled = true,
+            Semantics::Disable if conditional => self.enabled = false,
+            Semantics::Toggle if conditional => self.enabled = !self.enabled,
+            Semantics::Enable | Semantics::Disable | Semantics::Toggle => {}
+        }
+        Ok(None)
+    }
+
+    pub fn run(mut self, tokens: impl IntoIterator<Item = Result<Token, ArithmeticError>>) -> Result<A, ArithmeticError> {
+        for token in tokens {
+            self.step(&token?)?;
+        }
+        Ok(self.sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::lexer::Lexer;
+    use crate::memory::registry::InstructionSpec;
+
+    fn run(instructions: &InstructionSet, mode: Mode, input: &str) -> Result<u64, ArithmeticError> {
+        Interpreter::new(instructions, mode).run(Lexer::new(input, instructions))
+    }
+
+    #[test]
+    fn run_part_1() {
+        let instructions = InstructionSet::standard();
+        let input = "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+
+        assert_eq!(run(&instructions, Mode::IgnoreConditionals, input), Ok(161));
+    }
+
+    #[test]
+    fn run_part_2() {
+        let instructions = InstructionSet::standard();
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+        assert_eq!(run(&instructions, Mode::IgnoreConditionals, input), Ok(161));
+        assert_eq!(run(&instructions, Mode::HonourConditionals, input), Ok(48));
+    }
+
+    #[test]
+    fn run_registered_instructions() {
+        let instructions = InstructionSet::standard()
+            .register(InstructionSpec::new("add", 2, Semantics::Accumulate(|args| args[0].checked_add(args[1]))))
+            .register(InstructionSpec::new("sub", 2, Semantics::Accumulate(|args| args[0].checked_sub(args[1]))))
+            .register(InstructionSpec::new("toggle", 0, Semantics::Toggle));
+        let input = "add(1,2)toggle()mul(10,10)toggle()sub(9,4)don't()add(7,7)";
+
+        assert_eq!(run(&instructions, Mode::HonourConditionals, input), Ok(8));
+        assert_eq!(run(&instructions, Mode::IgnoreConditionals, input), Ok(122));
+    }
+
+    #[test]
+    fn run_reports_overflow() {
+        let instructions = InstructionSet::standard()
+            .register(InstructionSpec::new("big", 2, Semantics::Accumulate(|args| args[0].checked_mul(args[1]))));
+        let input = "mul(2,3)big(18446744073709551615,1)";
+
+        assert_eq!(run(&instructions, Mode::IgnoreConditionals, input), Err(ArithmeticError::Overflow { start: 8, end: 35 }));
+        assert_eq!(
+            run(&instructions, Mode::IgnoreConditionals, "big(4294967296,4294967296)"),
+            Err(ArithmeticError::Overflow { start: 0, end: 26 })
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn run_arbitrary_precision() {
+        let instructions = InstructionSet::standard()
+            .register(InstructionSpec::new("max", 0, Semantics::Accumulate(|_| Some(u64::MAX))));
+        let sum: num_bigint::BigUint = Interpreter::new(&instructions, Mode::IgnoreConditionals)
+            .run(Lexer::new("max()max()mul(2,1)", &instructions))
+            .unwrap();
+
+        assert_eq!(sum.to_string(), "36893488147419103232");
+    }
+}
@@ -0,0 +1,217 @@
+use super::{Instruction, NearMiss, Token};
+use super::arithmetic::ArithmeticError;
+use super::registry::{InstructionSet, InstructionSpec, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lexeme {
+    Token(Token),
+    NearMiss(NearMiss),
+    // A well-formed instruction whose operand does not fit in a `Value`.
+    Error(ArithmeticError),
+}
+
+
+pub struct Lexer<'a> {
+    input: &'a [u8],
+    instructions: &'a InstructionSet,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str, instructions: &'a InstructionSet) -> Lexer<'a> {
+        Lexer { input: input.as_bytes(), instructions, pos: 0 }
+    }
+
+    fn eat(&mut self, literal: &[u8]) -> bool {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            return true;
+        }
+        false
+    }
+
+    // `Some(None)` when the digits are valid but too large for a `Value`.
+    fn number(&mut self, spec: &InstructionSpec) -> Option<Option<Value>> {
+        let start = self.pos;
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        if !spec.digits.contains(&(self.pos - start)) {
+            return None;
+        }
+        Some(std::str::from_utf8(&self.input[start..self.pos]).ok()?.parse().ok())
+    }
+
+    fn arguments(&mut self, spec: &InstructionSpec) -> Option<Option<Vec<Value>>> {
+        let mut args = Some(Vec::with_capacity(spec.arity));
+        for i in 0..spec.arity {
+            if i > 0 && !self.eat(b",") {
+                return None;
+            }
+            let number = self.number(spec)?;
+            args = args.zip(number).map(|(mut args, number)| {
+                args.push(number);
+                args
+            });
+        }
+        if !self.eat(b")") {
+            return None;
+        }
+        Some(args)
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    // A near miss is a registered name followed by an opening parenthesis
+    // (optionally after whitespace) that still fails to form an instruction.
+    // `Ok(None)` is a well-formed instruction with an operand that overflows.
+    fn instruction_at(&mut self) -> Result<Option<Instruction>, Option<NearMiss>> {
+        let start = self.pos;
+        let mut near_miss = None;
+        for (opcode, spec) in self.instructions.iter() {
+            self.pos = start;
+            if !self.eat(spec.name.as_bytes()) {
+                continue;
+            }
+            let spaced = self.skip_whitespace();
+            if !self.eat(b"(") {
+                continue;
+            }
+            if !spaced {
+                if let Some(args) = self.arguments(spec) {
+                    return Ok(args.map(|args| Instruction { opcode, args }));
+                }
+            }
+            near_miss = near_miss.or(Some(NearMiss { opcode, start, end: self.pos.max(start + spec.name.len()) }));
+        }
+        Err(near_miss)
+    }
+
+    pub fn next_lexeme(&mut self) -> Option<Lexeme> {
+        while self.pos < self.input.len() {
+            let start = self.pos;
+            match self.instruction_at() {
+                Ok(Some(instruction)) => return Some(Lexeme::Token(Token { instruction, start, end: self.pos })),
+                Ok(None) => return Some(Lexeme::Error(ArithmeticError::OperandOverflow { start, end: self.pos })),
+                Err(near_miss) => {
+                    self.pos = start + 1;
+                    if let Some(near_miss) = near_miss {
+                        return Some(Lexeme::NearMiss(near_miss));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn lexemes(mut self) -> impl Iterator<Item = Lexeme> + 'a {
+        std::iter::from_fn(move || self.next_lexeme())
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, ArithmeticError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(lexeme) = self.next_lexeme() {
+            match lexeme {
+                Lexeme::Token(token) => return Some(Ok(token)),
+                Lexeme::Error(err) => return Some(Err(err)),
+                Lexeme::NearMiss(_) => {}
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::registry::Semantics;
+
+    fn names(instructions: &InstructionSet, input: &str) -> Vec<(String, Vec<Value>)> {
+        Lexer::new(input, instructions)
+            .map(Result::unwrap)
+            .map(|token| (instructions.get(token.instruction.opcode).name.clone(), token.instruction.args))
+            .collect()
+    }
+
+    #[test]
+    fn lex_example() {
+        let instructions = InstructionSet::standard();
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let tokens: Vec<(usize, usize)> = Lexer::new(input, &instructions)
+            .map(Result::unwrap)
+            .map(|token| (token.start, token.end))
+            .collect();
+
+        assert_eq!(tokens, vec![(1, 9), (20, 27), (28, 36), (48, 57), (59, 63), (64, 72)]);
+        assert_eq!(names(&instructions, input)[1], ("don't".to_string(), vec![]));
+    }
+
+    #[test]
+    fn lex_enforces_operand_length() {
+        let instructions = InstructionSet::standard();
+        let tokens: Vec<Token> = Lexer::new("mul(1234,5)mul(123,4)mul(,4)", &instructions).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].instruction.args, vec![123, 4]);
+    }
+
+    #[test]
+    fn lex_restarts_inside_broken_instruction() {
+        let instructions = InstructionSet::standard();
+        let tokens: Vec<Token> = Lexer::new("mul(1,mul(2,3)", &instructions).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].instruction.args, vec![2, 3]);
+        assert_eq!((tokens[0].start, tokens[0].end), (6, 14));
+    }
+
+    #[test]
+    fn lex_registered_instructions() {
+        let instructions = InstructionSet::new()
+            .register(InstructionSpec::new("add", 2, Semantics::Accumulate(|args| args[0].checked_add(args[1]))))
+            .register(InstructionSpec::new("mul", 3, Semantics::Accumulate(|args| args[0].checked_mul(args[1])?.checked_mul(args[2]))).with_digits(1..=3))
+            .register(InstructionSpec::new("toggle", 0, Semantics::Toggle));
+
+        assert_eq!(names(&instructions, "add(1,2)mul(1,2)mul(1,2,3)toggle()mul(1000,2,3)"), vec![
+            ("add".to_string(), vec![1, 2]),
+            ("mul".to_string(), vec![1, 2, 3]),
+            ("toggle".to_string(), vec![]),
+        ]);
+    }
+
+    #[test]
+    fn lex_near_misses() {
+        let instructions = InstructionSet::standard();
+        let input = "mul(4*mul ( 2 , 4 )do_not_mul[3,7]mul(2,4)";
+        let near_misses: Vec<(usize, usize)> = Lexer::new(input, &instructions)
+            .lexemes()
+            .filter_map(|lexeme| match lexeme {
+                Lexeme::NearMiss(near_miss) => Some((near_miss.start, near_miss.end)),
+                Lexeme::Token(_) | Lexeme::Error(_) => None,
+            })
+            .collect();
+
+        assert_eq!(near_misses, vec![(0, 5), (6, 11)]);
+        assert_eq!(Lexer::new(input, &instructions).count(), 1);
+    }
+
+    #[test]
+    fn lex_reports_operand_overflow() {
+        let instructions = InstructionSet::new()
+            .register(InstructionSpec::new("add", 2, Semantics::Accumulate(|args| args[0].checked_add(args[1]))));
+        let lexed: Vec<_> = Lexer::new("add(1,2)add(18446744073709551616,1)add(3,4", &instructions).collect();
+
+        assert_eq!(lexed.len(), 2);
+        assert!(lexed[0].is_ok());
+        assert_eq!(lexed[1], Err(ArithmeticError::OperandOverflow { start: 8, end: 35 }));
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::RangeInclusive;
+
+pub type Value = u64;
+
+#[derive(Clone, Copy)]
+pub enum Semantics {
+    Accumulate(fn(&[Value]) -> Option<Value>),
+    Enable,
+    Disable,
+    Toggle,
+}
+
+impl Semantics {
+    pub fn is_conditional(&self) -> bool {
+        !matches!(self, Semantics::Accumulate(_))
+    }
+}
+
+pub struct InstructionSpec {
+    pub name: String,
+    pub arity: usize,
+    pub digits: RangeInclusive<usize>,
+    pub semantics: Semantics,
+}
+
+impl InstructionSpec {
+    pub fn new(name: &str, arity: usize, semantics: Semantics) -> InstructionSpec {
+        InstructionSpec { name: name.to_string(), arity, digits: 1..=usize::MAX, semantics }
+    }
+
+    pub fn with_digits(mut self, digits: RangeInclusive<usize>) -> InstructionSpec {
+        self.digits = digits;
+        self
+    }
+}
+
+// Opcodes are registration slots, so they stay valid as more instructions
+// are registered; `match_order` is what the lexer tries first.
+#[derive(Default)]
+pub struct InstructionSet {
+    specs: Vec<InstructionSpec>,
+    match_order: Vec<usize>,
+}
+
+impl InstructionSet {
+    pub fn new() -> InstructionSet {
+        InstructionSet::default()
+    }
+
+    pub fn standard() -> InstructionSet {
+        InstructionSet::new()
+            .register(InstructionSpec::new("mul", 2, Semantics::Accumulate(|args| args[0].checked_mul(args[1]))).with_digits(1..=3))
+            .register(InstructionSpec::new("do", 0, Semantics::Enable))
+            .register(InstructionSpec::new("don't", 0, Semantics::Disable))
+    }
+
+    // The puzzle's instructions plus `add`, `sub` and `toggle`.
+    pub fn extended() -> InstructionSet {
+        InstructionSet::standard()
+            .register(InstructionSpec::new("add", 2, Semantics::Accumulate(|args| args[0].checked_add(args[1]))).with_digits(1..=3))
+            .register(InstructionSpec::new("sub", 2, Semantics::Accumulate(|args| args[0].checked_sub(args[1]))).with_digits(1..=3))
+            .register(InstructionSpec::new("toggle", 0, Semantics::Toggle))
+    }
+
+    // Re-registering a name replaces its spec but keeps its opcode.
+    pub fn register(mut self, spec: InstructionSpec) -> InstructionSet {
+        match self.opcode(&spec.name) {
+            Some(opcode) => self.specs[opcode] = spec,
+            None => {
+                self.specs.push(spec);
+                self.match_order.push(self.specs.len() - 1);
+            }
+        }
+        // Longer names first, so that `mulx(...)` is never shadowed by `mul`.
+        let specs = &self.specs;
+        self.match_order.sort_by_key(|&opcode| std::cmp::Reverse(specs[opcode].name.len()));
+        self
+    }
+
+    pub fn opcode(&self, name: &str) -> Option<usize> {
+        self.specs.iter().position(|spec| spec.name == name)
+    }
+
+    pub fn get(&self, opcode: usize) -> &InstructionSpec {
+        &self.specs[opcode]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &InstructionSpec)> {
+        self.match_order.iter().map(|&opcode| (opcode, &self.specs[opcode]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcodes_survive_registration() {
+        let instructions = InstructionSet::standard();
+        let mul = instructions.opcode("mul").unwrap();
+        let dont = instructions.opcode("don't").unwrap();
+
+        let instructions = instructions
+            .register(InstructionSpec::new("multiply", 2, Semantics::Accumulate(|args| args[0].checked_mul(args[1]))))
+            .register(InstructionSpec::new("mul", 2, Semantics::Accumulate(|args| args[0].checked_mul(args[1]))));
+
+        assert_eq!(instructions.opcode("mul"), Some(mul));
+        assert_eq!(instructions.opcode("don't"), Some(dont));
+        assert_eq!(instructions.get(mul).name, "mul");
+        assert_eq!(instructions.get(mul).digits, 1..=usize::MAX);
+        assert_eq!(instructions.iter().next().map(|(_, spec)| spec.name.as_str()), Some("multiply"));
+    }
+}
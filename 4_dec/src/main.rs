@@ -1,19 +1,49 @@
+mod pattern;
 mod string_windows;
+mod word_search;
 
-use string_windows::StringWindowExt;
+use pattern::{Pattern, Symmetry};
+use string_windows::{ByteGrid, Cells, GraphemeGrid, GridError, Ragged};
+use word_search::WordSearch;
 use load_input::read_file_contents;
 
+fn solve<'a, G: Cells<'a>>(grid: G) {
+    let xmas_count = WordSearch::new(grid, &["XMAS"]).count()["XMAS"];
+    println!("XMAS count: {}", xmas_count);
+
+    let mas_x_count = Pattern::compile("M.S/.A./M.S", Symmetry::Rotations).unwrap().count(grid);
+
+    println!("MAS-X count: {}", mas_x_count);
+}
+
 fn main() {
     let content = read_file_contents("input.txt").unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let filler = args.iter().position(|arg| arg == "--pad").and_then(|i| args.get(i + 1));
 
-    let mas_x_count = content.as_str().windows((3,3))
-        .into_iter()
-        .filter(|window| {
-            window[1][1] == 'A' &&
-            (window[0][0] == 'M' && window[2][2] == 'S' || window[0][0] == 'S' && window[2][2] == 'M') &&
-            (window[2][0] == 'M' && window[0][2] == 'S' || window[2][0] == 'S' && window[0][2] == 'M')
-        })
-        .count();
+    let result = match filler {
+        Some(filler) => GraphemeGrid::parse(&content, Ragged::Pad(filler)).map(|grid| solve(&grid)),
+        None => match ByteGrid::parse(&content) {
+            Err(GridError::NonAscii { .. }) => GraphemeGrid::parse(&content, Ragged::Reject).map(|grid| solve(&grid)),
+            grid => grid.map(solve),
+        },
+    };
 
-    println!("MAS-X count: {}", mas_x_count);
+    if let Err(err) = result {
+        println!("Invalid input: {}", err);
+    }
 }
+
+// The word search from the puzzle description, shared by the tests.
+#[cfg(test)]
+pub static EXAMPLE: &str =
+"MMMSXXMASM
+MSAMXMSMSA
+AMXSXMAAMM
+MSAMASMSMX
+XMASAMXAMM
+XXAMMXXAMA
+SMSMSASXSS
+SAXAMASAAA
+MAMMMXMMMM
+MXMXAXMASX";
@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::string_windows::{Cell, Cells, StringWindow, StringWindowExt};
+
+pub const WILDCARD: &str = ".";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    Rotations,
+    RotationsAndReflections,
+}
+
+// One orientation of a template; `None` cells match anything.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Template {
+    cells: Vec<Vec<Option<String>>>,
+}
+
+impl Template {
+    fn parse(template: &str) -> Result<Template, &'static str> {
+        let cells: Vec<Vec<Option<String>>> = template.split('/')
+            .map(|row| row.graphemes(true).map(|g| (g != WILDCARD).then(|| g.to_string())).collect())
+            .collect();
+
+        if cells[0].is_empty() {
+            return Err("Empty template");
+        }
+        if cells.iter().any(|row| row.len() != cells[0].len()) {
+            return Err("Template rows differ in length");
+        }
+        Ok(Template { cells })
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.cells.len(), self.cells[0].len())
+    }
+
+    fn rotate(&self) -> Template {
+        let (rows, cols) = self.size();
+        let cells = (0..cols)
+            .map(|c| (0..rows).rev().map(|r| self.cells[r][c].clone()).collect())
+            .collect();
+        Template { cells }
+    }
+
+    fn mirror(&self) -> Template {
+        let cells = self.cells.iter()
+            .map(|row| row.iter().rev().cloned().collect())
+            .collect();
+        Template { cells }
+    }
+
+    fn matches<'a, G: Cells<'a>>(&self, window: &StringWindow<'a, G>) -> bool {
+        self.cells.iter().enumerate().all(|(r, row)| {
+            row.iter().enumerate().all(|(c, cell)| cell.as_ref().is_none_or(|letter| window[r][c].is(letter)))
+        })
+    }
+}
+
+pub struct Pattern {
+    orientations: Vec<Template>,
+}
+
+impl Pattern {
+    pub fn compile(template: &str, symmetry: Symmetry) -> Result<Pattern, &'static str> {
+        let template = Template::parse(template)?;
+        let mut bases = vec![template.clone()];
+        if symmetry == Symmetry::RotationsAndReflections {
+            bases.push(template.mirror());
+        }
+
+        let mut seen = HashSet::new();
+        let mut orientations = Vec::new();
+        for base in bases {
+            let mut current = base;
+            for _ in 0..4 {
+                let next = current.rotate();
+                if seen.insert(current.clone()) {
+                    orientations.push(current);
+                }
+                current = next;
+            }
+        }
+        Ok(Pattern { orientations })
+    }
+
+    pub fn count<'a, G: Cells<'a>>(&self, grid: G) -> usize {
+        self.orientations.iter()
+            .map(|template| grid.windows(template.size()).filter(|window| template.matches(window)).count())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string_windows::{ByteGrid, GraphemeGrid, Ragged};
+    use crate::EXAMPLE;
+
+    #[test]
+    fn count_x_mas() {
+        let pattern = Pattern::compile("M.S/.A./M.S", Symmetry::Rotations).unwrap();
+
+        assert_eq!(pattern.orientations.len(), 4);
+        assert_eq!(pattern.count(ByteGrid::parse(EXAMPLE).unwrap()), 9);
+    }
+
+    #[test]
+    fn reflections_are_deduplicated() {
+        let x_mas = Pattern::compile("M.S/.A./M.S", Symmetry::RotationsAndReflections).unwrap();
+        let symmetric = Pattern::compile("A.A/.A./A.A", Symmetry::RotationsAndReflections).unwrap();
+        let l_shape = Pattern::compile("X./X./XX", Symmetry::RotationsAndReflections).unwrap();
+
+        assert_eq!(x_mas.orientations.len(), 4);
+        assert_eq!(symmetric.orientations.len(), 1);
+        assert_eq!(l_shape.orientations.len(), 8);
+    }
+
+    #[test]
+    fn count_non_square() {
+        let pattern = Pattern::compile("XMAS", Symmetry::Rotations).unwrap();
+
+        assert_eq!(pattern.orientations.len(), 4);
+        assert_eq!(pattern.count(ByteGrid::parse(EXAMPLE).unwrap()), 3 + 2 + 1 + 2);
+    }
+
+    #[test]
+    fn compile_rejects_ragged_template() {
+        assert!(Pattern::compile("M.S/.A/M.S", Symmetry::Rotations).is_err());
+    }
+
+    #[test]
+    fn count_unicode_template() {
+        let grid = GraphemeGrid::parse("ñ..\n.λ.\nñ.ñ", Ragged::Reject).unwrap();
+        let pattern = Pattern::compile("ñ.ñ/.λ./ñ..", Symmetry::Rotations).unwrap();
+
+        assert_eq!(pattern.count(&grid), 1);
+    }
+}
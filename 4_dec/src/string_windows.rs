@@ -1,38 +1,263 @@
-pub type StringWindow = Vec<Vec<char>>;
+use std::fmt;
+use std::ops::Index;
 
-pub trait StringWindowExt {
-    fn windows(&self, window_size: (usize,usize)) -> impl Iterator<Item = StringWindow>;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridError {
+    Ragged { line: usize, expected: usize, found: usize },
+    MixedLineEndings { line: usize },
+    NonAscii { line: usize, column: usize },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Ragged { line, expected, found } =>
+                write!(f, "line {} has {} cells, expected {}", line, found, expected),
+            GridError::MixedLineEndings { line } =>
+                write!(f, "line {} uses a different line ending than line 1", line),
+            GridError::NonAscii { line, column } =>
+                write!(f, "non-ASCII character at line {}, column {}; use a GraphemeGrid", line, column),
+        }
+    }
 }
 
-impl StringWindowExt for &str {
-    fn windows(&self, window_size: (usize,usize)) -> impl Iterator<Item = StringWindow> {
-        let lines: Vec<String> = self.lines().map(|line| line.to_string()).collect();
-        let line_windows: Vec<Vec<String>> = lines.windows(window_size.0).map(|w| w.to_vec()).collect();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ragged<'f> {
+    Reject,
+    Pad(&'f str),
+}
 
-        line_windows.into_iter().flat_map(move |lw| {
-            let mut result = Vec::new();
+pub trait Cell: Copy + PartialEq {
+    fn as_str(&self) -> &str;
 
-            let char_windows: Vec<_> = lw.iter().map(|l| {
-                l.chars().collect::<Vec<_>>()
-                .windows(window_size.1)
-                .map(|x| x.to_vec())
-                .collect::<Vec<_>>()
-            }).collect();
+    fn is(&self, letter: &str) -> bool {
+        self.as_str() == letter
+    }
+}
 
-            let mut iterators: Vec<_> = char_windows.iter().map(|cw| cw.iter().peekable()).collect();
+impl Cell for u8 {
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(std::slice::from_ref(self)).unwrap_or("\u{fffd}")
+    }
+}
+
+impl Cell for &str {
+    fn as_str(&self) -> &str {
+        self
+    }
+}
+
+// Anything that can hand out rows of cells for as long as `'a`.
+pub trait Cells<'a>: Copy {
+    type Cell: Cell + 'a;
+
+    fn size(&self) -> (usize, usize);
+    fn row(&self, row: usize) -> &'a [Self::Cell];
+}
+
+// A rectangular view over the lines of an ASCII `&str`. Lines must have the
+// same length and share the same line ending, so a cell can be addressed
+// directly in the original bytes as `row * stride + col`.
+#[derive(Debug, Clone, Copy)]
+pub struct ByteGrid<'a> {
+    bytes: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl<'a> ByteGrid<'a> {
+    pub fn parse(text: &'a str) -> Result<ByteGrid<'a>, GridError> {
+        let mut width = None;
+        let mut stride = None;
+        let mut height = 0;
+
+        for (index, raw) in text.split_inclusive('\n').enumerate() {
+            let line = index + 1;
+            let content = raw.strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(raw);
+
+            if let Some(column) = content.bytes().position(|b| !b.is_ascii()) {
+                return Err(GridError::NonAscii { line, column: column + 1 });
+            }
+            let expected = *width.get_or_insert(content.len());
+            if content.len() != expected {
+                return Err(GridError::Ragged { line, expected, found: content.len() });
+            }
+            if raw.len() > content.len() && *stride.get_or_insert(raw.len()) != raw.len() {
+                return Err(GridError::MixedLineEndings { line });
+            }
+            height += 1;
+        }
+
+        let width = width.unwrap_or(0);
+        Ok(ByteGrid { bytes: text.as_bytes(), width, height, stride: stride.unwrap_or(width + 1) })
+    }
+}
+
+impl<'a> Cells<'a> for ByteGrid<'a> {
+    type Cell = u8;
+
+    fn size(&self) -> (usize, usize) {
+        (self.height, self.width)
+    }
 
-            while iterators.iter().all(|it| it.clone().peek().is_some()) {
-                let mut line_window = Vec::new();
-                for it in iterators.iter_mut() {
-                    if let Some(window) = it.next() {
-                        line_window.push(window.clone());
-                    }
+    fn row(&self, row: usize) -> &'a [u8] {
+        let start = row * self.stride;
+        &self.bytes[start..start + self.width]
+    }
+}
+
+// A grid of extended grapheme clusters, so that e.g. `é` written as `e` plus a
+// combining accent is a single cell. Cells borrow from the input text.
+#[derive(Debug, Clone)]
+pub struct GraphemeGrid<'a> {
+    cells: Vec<&'a str>,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> GraphemeGrid<'a> {
+    pub fn parse(text: &'a str, ragged: Ragged<'a>) -> Result<GraphemeGrid<'a>, GridError> {
+        let lines: Vec<Vec<&'a str>> = text.lines().map(|line| line.graphemes(true).collect()).collect();
+        let width = match ragged {
+            Ragged::Reject => lines.first().map_or(0, Vec::len),
+            Ragged::Pad(_) => lines.iter().map(Vec::len).max().unwrap_or(0),
+        };
+
+        let mut cells = Vec::with_capacity(width * lines.len());
+        for (index, line) in lines.iter().enumerate() {
+            match ragged {
+                Ragged::Reject if line.len() != width =>
+                    return Err(GridError::Ragged { line: index + 1, expected: width, found: line.len() }),
+                Ragged::Reject => cells.extend_from_slice(line),
+                Ragged::Pad(filler) => {
+                    cells.extend_from_slice(line);
+                    cells.resize(cells.len() + width - line.len(), filler);
                 }
-                result.push(line_window);
             }
+        }
+
+        Ok(GraphemeGrid { cells, width, height: lines.len() })
+    }
+}
+
+impl<'g, 'a: 'g> Cells<'g> for &'g GraphemeGrid<'a> {
+    type Cell = &'a str;
+
+    fn size(&self) -> (usize, usize) {
+        (self.height, self.width)
+    }
+
+    fn row(&self, row: usize) -> &'g [&'a str] {
+        let start = row * self.width;
+        &self.cells[start..start + self.width]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct StringWindow<'a, G: Cells<'a> = ByteGrid<'a>> {
+    grid: G,
+    origin: (usize, usize),
+    size: (usize, usize),
+    marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl<'a, G: Cells<'a>> StringWindow<'a, G> {
+    pub fn origin(&self) -> (usize, usize) {
+        self.origin
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [G::Cell]> + '_ {
+        (0..self.size.0).map(|row| self.row(row))
+    }
+
+    fn row(&self, row: usize) -> &'a [G::Cell] {
+        let col = self.origin.1;
+        &self.grid.row(self.origin.0 + row)[col..col + self.size.1]
+    }
+}
+
+// `ByteGrid::parse` only admits ASCII, so every byte is a whole char.
+impl StringWindow<'_> {
+    pub fn to_vec(self) -> Vec<Vec<char>> {
+        self.rows().map(|row| row.iter().map(|&b| char::from(b)).collect()).collect()
+    }
+}
+
+impl<'a, G: Cells<'a>> Index<usize> for StringWindow<'a, G> {
+    type Output = [G::Cell];
 
-            result.into_iter()
-        })
+    fn index(&self, row: usize) -> &[G::Cell] {
+        self.row(row)
+    }
+}
+
+impl PartialEq<Vec<Vec<char>>> for StringWindow<'_> {
+    fn eq(&self, other: &Vec<Vec<char>>) -> bool {
+        self.to_vec() == *other
+    }
+}
+
+impl<'a, G: Cells<'a>> fmt::Debug for StringWindow<'a, G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self.rows().map(|row| row.iter().map(Cell::as_str).collect::<String>());
+        f.debug_list().entries(rows).finish()
+    }
+}
+
+pub struct Windows<'a, G: Cells<'a> = ByteGrid<'a>> {
+    grid: G,
+    size: (usize, usize),
+    next: (usize, usize),
+    marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl<'a, G: Cells<'a>> Iterator for Windows<'a, G> {
+    type Item = StringWindow<'a, G>;
+
+    fn next(&mut self) -> Option<StringWindow<'a, G>> {
+        let (height, width) = self.grid.size();
+        if self.size.0 == 0 || self.size.1 == 0
+            || width < self.size.1
+            || self.next.0 + self.size.0 > height {
+            return None;
+        }
+
+        let origin = self.next;
+        self.next.1 += 1;
+        if self.next.1 + self.size.1 > width {
+            self.next = (self.next.0 + 1, 0);
+        }
+
+        Some(StringWindow { grid: self.grid, origin, size: self.size, marker: std::marker::PhantomData })
+    }
+}
+
+pub trait StringWindowExt<'a> {
+    type Windows;
+
+    fn windows(self, window_size: (usize,usize)) -> Self::Windows;
+}
+
+// Text has to be checked first; use `GraphemeGrid::parse` for input that is
+// not a rectangular ASCII grid.
+impl<'a> StringWindowExt<'a> for &'a str {
+    type Windows = Result<Windows<'a>, GridError>;
+
+    fn windows(self, window_size: (usize,usize)) -> Result<Windows<'a>, GridError> {
+        ByteGrid::parse(self).map(|grid| grid.windows(window_size))
+    }
+}
+
+impl<'a, G: Cells<'a>> StringWindowExt<'a> for G {
+    type Windows = Windows<'a, G>;
+
+    fn windows(self, window_size: (usize,usize)) -> Windows<'a, G> {
+        Windows { grid: self, size: window_size, next: (0, 0), marker: std::marker::PhantomData }
     }
 }
 
@@ -44,7 +269,7 @@ mod tests {
     fn test_windows_22() {
         let input = "abc\ndef\nghi\n";
 
-        let result: Vec<_> = input.windows((2,2)).collect();
+        let result: Vec<_> = input.windows((2,2)).unwrap().collect();
 
         assert_eq!(result, vec![
             vec![vec!['a','b'], vec!['d','e']],
@@ -58,7 +283,7 @@ mod tests {
     fn test_windows_33() {
         let input = "abcd\nefgh\nijkl\nmnop";
 
-        let result: Vec<_> = input.windows((3,3)).collect();
+        let result: Vec<_> = input.windows((3,3)).unwrap().collect();
 
         assert_eq!(result, vec![
             vec![vec!['a','b','c'], vec!['e', 'f','g'], vec!['i','j', 'k']],
@@ -67,4 +292,78 @@ mod tests {
             vec![vec!['f','g','h'], vec!['j','k','l'], vec!['n','o','p']]
         ]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_windows_crlf() {
+        let input = "abc\r\ndef\r\nghi";
+
+        let result: Vec<_> = input.windows((2,3)).unwrap().map(|window| window.to_vec()).collect();
+
+        assert_eq!(result, vec![
+            vec![vec!['a','b','c'], vec!['d','e','f']],
+            vec![vec!['d','e','f'], vec!['g','h','i']],
+        ]);
+    }
+
+    #[test]
+    fn test_windows_larger_than_grid() {
+        assert_eq!("ab\ncd".windows((3,1)).unwrap().count(), 0);
+        assert_eq!("ab\ncd".windows((1,3)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_parse_rejects_ragged() {
+        assert_eq!(
+            ByteGrid::parse("abc\nde\nfgh").unwrap_err(),
+            GridError::Ragged { line: 2, expected: 3, found: 2 }
+        );
+        assert_eq!(
+            GraphemeGrid::parse("abc\nabc\nabcd", Ragged::Reject).unwrap_err(),
+            GridError::Ragged { line: 3, expected: 3, found: 4 }
+        );
+        assert_eq!(ByteGrid::parse("ab\r\ncd\nef").unwrap_err(), GridError::MixedLineEndings { line: 2 });
+        assert_eq!(ByteGrid::parse("ab\ncé").unwrap_err(), GridError::NonAscii { line: 2, column: 2 });
+    }
+
+    #[test]
+    fn test_parse_checks_every_row() {
+        assert_eq!(
+            ByteGrid::parse("abc\ndef\nghij\n").unwrap_err(),
+            GridError::Ragged { line: 3, expected: 3, found: 4 }
+        );
+        assert_eq!(
+            ByteGrid::parse("abc\ndef\n\n").unwrap_err(),
+            GridError::Ragged { line: 3, expected: 3, found: 0 }
+        );
+        assert_eq!(ByteGrid::parse("ab\ncd\nef\ngh\u{e9}").unwrap_err(), GridError::NonAscii { line: 4, column: 3 });
+    }
+
+    #[test]
+    fn test_windows_ragged_str() {
+        assert_eq!("abc\nde".windows((1,1)).err(), Some(GridError::Ragged { line: 2, expected: 3, found: 2 }));
+    }
+
+    #[test]
+    fn test_windows_padded() {
+        let grid = GraphemeGrid::parse("abc\nd\nef", Ragged::Pad("#")).unwrap();
+
+        let result: Vec<String> = grid.windows((3,1))
+            .map(|window| window.rows().map(|row| row[0]).collect())
+            .collect();
+
+        assert_eq!(result, vec!["ade", "b#f", "c##"]);
+    }
+
+    #[test]
+    fn test_windows_graphemes() {
+        let input = "ñe\u{301}x\nωψ🦀";
+        let grid = GraphemeGrid::parse(input, Ragged::Reject).unwrap();
+
+        let result: Vec<_> = grid.windows((2,2)).map(|window| format!("{:?}", window)).collect();
+
+        assert_eq!(result, vec![
+            format!("{:?}", ["ñe\u{301}", "ωψ"]),
+            format!("{:?}", ["e\u{301}x", "ψ🦀"]),
+        ]);
+    }
+}
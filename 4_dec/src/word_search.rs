@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::string_windows::{Cell, Cells, StringWindowExt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction { E, W, S, N, SE, NW, SW, NE }
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::E, Direction::W, Direction::S, Direction::N,
+        Direction::SE, Direction::NW, Direction::SW, Direction::NE,
+    ];
+
+    // Reading a word in one of these gives the same cells as reading it
+    // backwards in the matching reverse direction.
+    const FORWARD: [Direction; 4] = [Direction::E, Direction::S, Direction::SE, Direction::SW];
+
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::E => (0, 1),
+            Direction::W => (0, -1),
+            Direction::S => (1, 0),
+            Direction::N => (-1, 0),
+            Direction::SE => (1, 1),
+            Direction::NW => (-1, -1),
+            Direction::SW => (1, -1),
+            Direction::NE => (-1, 1),
+        }
+    }
+
+    fn window_size(self, len: usize) -> (usize, usize) {
+        let (dr, dc) = self.delta();
+        (1 + (len - 1) * dr.unsigned_abs(), 1 + (len - 1) * dc.unsigned_abs())
+    }
+
+    // Position of the i-th letter inside a window of `window_size(len)`.
+    fn cell(self, i: usize, len: usize) -> (usize, usize) {
+        let along = |d: isize| match d {
+            0 => 0,
+            d if d > 0 => i,
+            _ => len - 1 - i,
+        };
+        let (dr, dc) = self.delta();
+        (along(dr), along(dc))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WordMatch {
+    pub word: String,
+    pub start: (usize, usize),
+    pub direction: Direction,
+}
+
+pub struct WordSearch<G> {
+    grid: G,
+    words: Vec<String>,
+}
+
+impl<'a, G: Cells<'a>> WordSearch<G> {
+    pub fn new(grid: G, words: &[&str]) -> WordSearch<G> {
+        let mut seen = HashSet::new();
+        let words = words.iter()
+            .filter(|word| !word.is_empty() && seen.insert(**word))
+            .map(|word| word.to_string())
+            .collect();
+        WordSearch { grid, words }
+    }
+
+    fn directions(word: &[&str]) -> &'static [Direction] {
+        if word.len() == 1 {
+            return &[Direction::E];
+        }
+        if word.iter().eq(word.iter().rev()) {
+            return &Direction::FORWARD;
+        }
+        &Direction::ALL
+    }
+
+    fn find_word(&self, word: &str) -> Vec<WordMatch> {
+        let letters: Vec<&str> = word.graphemes(true).collect();
+        let len = letters.len();
+
+        Self::directions(&letters).iter().flat_map(|&direction| {
+            let letters = &letters;
+            self.grid.windows(direction.window_size(len))
+                .filter(move |window| {
+                    (0..len).all(|i| {
+                        let (row, col) = direction.cell(i, len);
+                        window[row][col].is(letters[i])
+                    })
+                })
+                .map(move |window| {
+                    let (row, col) = direction.cell(0, len);
+                    let origin = window.origin();
+                    WordMatch { word: word.to_string(), start: (origin.0 + row, origin.1 + col), direction }
+                })
+        }).collect()
+    }
+
+    pub fn find(&self) -> Vec<WordMatch> {
+        self.words.iter().flat_map(|word| self.find_word(word)).collect()
+    }
+
+    pub fn count(&self) -> HashMap<String, usize> {
+        let mut count: HashMap<String, usize> = self.words.iter().map(|word| (word.clone(), 0)).collect();
+        for found in self.find() {
+            *count.entry(found.word).or_insert(0) += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string_windows::{ByteGrid, GraphemeGrid, Ragged};
+    use crate::EXAMPLE;
+
+    #[test]
+    fn find_xmas() {
+        let search = WordSearch::new(ByteGrid::parse(EXAMPLE).unwrap(), &["XMAS"]);
+
+        assert_eq!(search.find().len(), 18);
+        assert_eq!(search.count()["XMAS"], 18);
+    }
+
+    #[test]
+    fn find_reports_start_and_direction() {
+        let search = WordSearch::new(ByteGrid::parse("XMAS\n..A.\n.M..\nX...").unwrap(), &["XMAS"]);
+        let matches: HashSet<_> = search.find().into_iter().map(|m| (m.start, m.direction)).collect();
+
+        assert_eq!(matches, HashSet::from([((0, 0), Direction::E), ((3, 0), Direction::NE)]));
+    }
+
+    #[test]
+    fn find_palindromes_once() {
+        let search = WordSearch::new(ByteGrid::parse("ABABA\nB...B\nA...A").unwrap(), &["ABA", "A", "ABA"]);
+        let count = search.count();
+
+        assert_eq!(count["ABA"], 4);
+        assert_eq!(count["A"], 5);
+        assert_eq!(count.len(), 2);
+    }
+
+    #[test]
+    fn find_overlapping() {
+        let search = WordSearch::new(ByteGrid::parse("AAAA").unwrap(), &["AA", "AB"]);
+
+        assert_eq!(search.count()["AA"], 3);
+        assert_eq!(search.count()["AB"], 0);
+    }
+
+    #[test]
+    fn find_unicode_words() {
+        let grid = GraphemeGrid::parse("ΑΒΓ\nΒñΔ\nΓΔ", Ragged::Pad(" ")).unwrap();
+        let search = WordSearch::new(&grid, &["ΑΒΓ", "ñΔ"]);
+        let count = search.count();
+
+        assert_eq!(count["ΑΒΓ"], 2);
+        assert_eq!(count["ñΔ"], 2);
+    }
+}
@@ -0,0 +1,11 @@
+// Shared by the rule and input parsers.
+#[derive(PartialEq, Debug, Clone)]
+pub enum ParseErrorKind {
+    MalformedRule(String),
+    SelfReferentialRule(usize),
+    MalformedPage(String),
+    EmptyUpdate,
+    DuplicatePage(usize),
+    EvenLengthUpdate(usize),
+    MissingUpdates,
+}
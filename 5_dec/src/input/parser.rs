@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::ParseErrorKind;
+use crate::rules::model::Rule;
+use crate::rules::parser::parse_rule_line;
+use crate::updates::model::Updates;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Section {
+    Rules,
+    Updates,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub section: Section,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self.section {
+            Section::Rules => "rules",
+            Section::Updates => "updates",
+        };
+        write!(f, "{} section, line {}, column {}: ", section, self.line, self.column)?;
+        match &self.kind {
+            ParseErrorKind::MalformedRule(reason) => write!(f, "malformed rule, {}", reason),
+            ParseErrorKind::SelfReferentialRule(page) => write!(f, "rule {}|{} refers to itself", page, page),
+            ParseErrorKind::MalformedPage(page) => write!(f, "'{}' is not a page number", page),
+            ParseErrorKind::EmptyUpdate => write!(f, "empty update"),
+            ParseErrorKind::DuplicatePage(page) => write!(f, "page {} appears twice", page),
+            ParseErrorKind::EvenLengthUpdate(len) => write!(f, "update has {} pages, so it has no middle page", len),
+            ParseErrorKind::MissingUpdates => write!(f, "expected a blank line followed by updates"),
+        }
+    }
+}
+
+fn parse_update_line(line: &str) -> Result<Updates, (usize, ParseErrorKind)> {
+    let mut update = Vec::new();
+    let mut first_seen: HashMap<usize, usize> = HashMap::new();
+    let mut column = 1;
+
+    for field in line.split(',') {
+        let trimmed = field.trim_start();
+        let page_column = column + field.len() - trimmed.len();
+        let page: usize = trimmed.trim_end().parse()
+            .map_err(|_| (page_column, ParseErrorKind::MalformedPage(trimmed.trim_end().to_string())))?;
+        if first_seen.insert(page, page_column).is_some() {
+            return Err((page_column, ParseErrorKind::DuplicatePage(page)));
+        }
+        update.push(page);
+        column += field.len() + 1;
+    }
+
+    if update.len() % 2 == 0 {
+        return Err((1, ParseErrorKind::EvenLengthUpdate(update.len())));
+    }
+    Ok(update)
+}
+
+// Reads the rules section, a blank line and the updates section in a single
+// pass over the lines. Leading and trailing blank lines are ignored.
+pub fn parse_input(content: &str) -> Result<(Vec<Rule>, Vec<Updates>), ParseError> {
+    let mut rules = Vec::new();
+    let mut updates = Vec::new();
+    let mut section = Section::Rules;
+    let mut blank_line = None;
+    let mut last_line = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        last_line = line_no;
+        let error = |column, kind| ParseError { section, line: line_no, column, kind };
+
+        if line.trim().is_empty() {
+            match section {
+                Section::Rules if rules.is_empty() => {},
+                Section::Rules => section = Section::Updates,
+                Section::Updates => { blank_line.get_or_insert(line_no); },
+            }
+            continue;
+        }
+
+        match section {
+            Section::Rules => {
+                rules.push(parse_rule_line(line).map_err(|(column, kind)| error(column, kind))?);
+            },
+            Section::Updates => {
+                if let Some(blank) = blank_line {
+                    return Err(ParseError { section, line: blank, column: 1, kind: ParseErrorKind::EmptyUpdate });
+                }
+                updates.push(parse_update_line(line).map_err(|(column, kind)| error(column, kind))?);
+            },
+        }
+    }
+
+    if updates.is_empty() {
+        return Err(ParseError { section, line: last_line, column: 1, kind: ParseErrorKind::MissingUpdates });
+    }
+    Ok((rules, updates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(content: &str) -> (Section, usize, usize, ParseErrorKind) {
+        let err = parse_input(content).unwrap_err();
+        (err.section, err.line, err.column, err.kind)
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let (rules, updates) = parse_input("1|2\r\n3|4\r\n\r\n1,2,3\r\n5\r\n\r\n").unwrap();
+
+        assert_eq!(rules, vec![Rule::new(1, 2), Rule::new(3, 4)]);
+        assert_eq!(updates, vec![vec![1, 2, 3], vec![5]]);
+    }
+
+    #[test]
+    fn test_leading_blank_lines() {
+        let (rules, updates) = parse_input("\n  \n1|2\n\n1,2,3").unwrap();
+
+        assert_eq!(rules, vec![Rule::new(1, 2)]);
+        assert_eq!(updates, vec![vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn test_malformed_rule() {
+        assert_eq!(
+            parse_error("1|2\n3|4|5\n\n1,2,3"),
+            (Section::Rules, 2, 4, ParseErrorKind::MalformedRule("expected end of line".to_string()))
+        );
+        assert_eq!(
+            parse_error("1|2\n3-4\n\n1,2,3"),
+            (Section::Rules, 2, 2, ParseErrorKind::MalformedRule("expected '|'".to_string()))
+        );
+        assert_eq!(
+            parse_error("x|2\n\n1,2,3"),
+            (Section::Rules, 1, 1, ParseErrorKind::MalformedRule("expected a page number".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_self_referential_rule() {
+        assert_eq!(parse_error("1|2\n4|4\n\n1"), (Section::Rules, 2, 1, ParseErrorKind::SelfReferentialRule(4)));
+    }
+
+    #[test]
+    fn test_update_errors() {
+        assert_eq!(parse_error("1|2\n\n1,2,3\n\n4,5,6"), (Section::Updates, 4, 1, ParseErrorKind::EmptyUpdate));
+        assert_eq!(parse_error("1|2\n\n1,2,3\n4,5,4"), (Section::Updates, 4, 5, ParseErrorKind::DuplicatePage(4)));
+        assert_eq!(parse_error("1|2\n\n1,2,3\n4,5"), (Section::Updates, 4, 1, ParseErrorKind::EvenLengthUpdate(2)));
+        assert_eq!(
+            parse_error("1|2\n\n1, x,3"),
+            (Section::Updates, 3, 4, ParseErrorKind::MalformedPage("x".to_string()))
+        );
+        assert_eq!(parse_error("1|2\n3|4"), (Section::Rules, 2, 1, ParseErrorKind::MissingUpdates));
+    }
+
+    #[test]
+    fn test_display() {
+        let err = parse_input("1|2\n\n1,2,3\n4,5,4").unwrap_err();
+
+        assert_eq!(err.to_string(), "updates section, line 4, column 5: page 4 appears twice");
+    }
+}
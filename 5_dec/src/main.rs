@@ -1,46 +1,38 @@
+mod error;
+
+mod input {
+    pub mod parser;
+}
+
 mod rules {
     pub mod model;
     pub mod validation;
     pub mod parser;
+    pub mod graph;
+    pub mod set;
 }
 
 mod updates {
     pub mod model;
+    pub mod repair;
+    pub mod dot;
+    pub mod orderings;
 }
 
 use load_input::read_file_contents;
-use rules::parser::RuleParser;
+use input::parser::parse_input;
+use rules::graph::{PrecedenceGraph, SortError};
 use rules::model::Rule;
+use rules::set::RuleSet;
+use rules::validation::IndexedRules;
 use updates::model::{
     Updates,
     UpdateValidator,
     RuleSolver
 };
-
-fn parse_content(content: &str) -> Result<(Vec<Rule>, Vec<Updates>), &str> {
-    let mut content_iter = content.split("\r\n\r\n").flat_map(|s| s.split("\n\n"));
-    
-    let rules = match content_iter.next() {
-        Some(rule_content) => match rule_content.parse_rules(){
-            Some(rules) => rules,
-            _ => return Err("Error parsing rules")
-        },
-        _ => return Err("Error finding rule content")   
-    };
-    
-    let updates: Vec<Updates> = match content_iter.next(){
-
-        Some(updates) => {
-            updates
-                .lines()
-                .map(|line| line.split(",").map(|x| x.parse().unwrap()).collect())
-                .collect()
-        },
-        _ => return Err("Failed to find updates")
-    };
-
-    Ok((rules, updates))
-}
+use updates::dot::UpdateGraph;
+use updates::orderings::UpdateOrderings;
+use updates::repair::UpdateRepairer;
 
 fn get_middle (updates: &Updates) -> usize {
     updates[updates.len()/2]
@@ -49,59 +41,160 @@ fn get_middle (updates: &Updates) -> usize {
 
 fn main() {
     let contents = read_file_contents("input.txt").unwrap();
-    let (rules, updates) = parse_content(&contents).unwrap();
+    let (rules, updates) = match parse_input(&contents) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("Invalid input: {}", err);
+            return;
+        }
+    };
 
-    let valid_result = task_1(&rules, &updates);
-    let soved_result = task_2(&rules, &updates);
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("rules") {
+        query_rules(&RuleSet::new(&rules), &args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("orderings") {
+        list_orderings(&rules, &updates, &args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("dot") {
+        write_dot(&RuleSet::new(&rules), &updates, &args[2..]);
+        return;
+    }
+
+    let indexed_rules = IndexedRules::new(&rules);
+    let valid_result = task_1(&indexed_rules, &updates);
+    let (soved_result, skipped) = task_2(&indexed_rules, &updates);
+    for (update, err) in &skipped {
+        println!("Skipping {:?}: {}", update, err);
+    }
     
     println!("Valid mid-sum Result: {}", valid_result.iter().map(get_middle).sum::<usize>());
     println!("Solved mid-sum Result: {}", soved_result.iter().map(get_middle).sum::<usize>());
+
+    let graph = PrecedenceGraph::new(&rules);
+    // Updates with cyclic rules were already reported as skipped by task 2.
+    let ambiguous = updates.iter().filter(|update| update.has_unique_ordering(&graph) == Ok(false)).count();
+    if ambiguous > 0 {
+        println!("Warning: {} updates have more than one valid ordering", ambiguous);
+    }
+    if !skipped.is_empty() {
+        println!("Warning: {} updates have cyclic rules and no valid ordering", skipped.len());
+    }
+
+    if std::env::args().any(|arg| arg == "--explain") {
+        explain_invalid(&rules, &indexed_rules, &updates);
+    }
 }
 
-fn task_2(rules: &Vec<Rule>, updates: &Vec<Updates>) -> Vec<Vec<usize>> {
-    updates.iter().filter(|ue| !ue.validate(rules).is_valid())
-        .map(|ue| ue.solve(rules))
-        .collect()
+fn query_rules(rule_set: &RuleSet, args: &[String]) {
+    let pages: Vec<usize> = args.iter().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+
+    match (args.first().map(String::as_str), pages.as_slice()) {
+        (Some("precedes"), &[a, b]) => println!("{} must come before {}: {}", a, b, rule_set.must_precede(a, b)),
+        (Some("unconstrained"), _) => {
+            for (a, b) in rule_set.unconstrained() {
+                println!("{} {}", a, b);
+            }
+        },
+        (Some("redundant"), _) => {
+            for rule in rule_set.redundant() {
+                println!("{}|{}", rule.x, rule.y);
+            }
+        },
+        (Some("reduce"), _) => {
+            for rule in rule_set.transitive_reduction().rules() {
+                println!("{}|{}", rule.x, rule.y);
+            }
+        },
+        _ => println!("Usage: aoc_5 rules <precedes A B | unconstrained | redundant | reduce>"),
+    }
 }
 
-fn task_1(rules: &Vec<Rule>, updates: &Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+fn list_orderings(rules: &[Rule], updates: &[Updates], args: &[String]) {
+    let Some(update) = args.first().and_then(|i| i.parse::<usize>().ok()).and_then(|i| updates.get(i)) else {
+        println!("Usage: aoc_5 orderings <update index> [limit]");
+        return;
+    };
+    let limit = args.get(1).and_then(|l| l.parse().ok()).unwrap_or(10);
+
+    for ordering in update.orderings(rules).take(limit) {
+        println!("{:?}", ordering);
+    }
+}
+
+fn write_dot(rule_set: &RuleSet, updates: &[Updates], args: &[String]) {
+    let Some(path) = args.first() else {
+        println!("Usage: aoc_5 dot <file> [update index]");
+        return;
+    };
+    let dot = match args.get(1).and_then(|i| i.parse::<usize>().ok()) {
+        Some(i) if i < updates.len() => updates[i].to_dot(rule_set),
+        Some(i) => {
+            println!("No update with index {}", i);
+            return;
+        },
+        None => rule_set.to_dot(),
+    };
+
+    match std::fs::write(path, dot) {
+        Ok(()) => println!("Wrote {}", path),
+        Err(err) => println!("Failed to write {}: {}", path, err),
+    }
+}
+
+fn explain_invalid(rules: &[Rule], indexed_rules: &IndexedRules, updates: &[Updates]) {
+    for update in updates {
+        let validation = update.validate(indexed_rules);
+        if validation.is_valid() {
+            continue;
+        }
+        println!("{:?}", update);
+        for violation in validation.explain() {
+            println!("  {}", violation);
+        }
+        match update.repair_minimal(indexed_rules) {
+            Ok(repair) => println!("  repaired with {} adjacent swaps: {:?}", repair.swaps.len(), repair.order),
+            Err(err) => println!("  cannot repair: {}", err),
+        }
+        match update.count_orderings(rules) {
+            Ok(count) => println!("  {} valid orderings", count),
+            Err(err) => println!("  {}", err),
+        }
+    }
+}
+
+// Solves every invalid update. Updates whose rules form a cycle have no
+// valid order and are returned separately.
+fn task_2<'a>(rules: &IndexedRules, updates: &'a [Updates]) -> (Vec<Vec<usize>>, Vec<(&'a Updates, SortError)>) {
+    let mut solved = Vec::new();
+    let mut skipped = Vec::new();
+    for ue in updates.iter().filter(|ue| !ue.validate(rules).is_valid()) {
+        match ue.solve(rules) {
+            Ok(order) => solved.push(order),
+            Err(err) => skipped.push((ue, err)),
+        }
+    }
+    (solved, skipped)
+}
+
+fn task_1(rules: &IndexedRules, updates: &[Updates]) -> Vec<Vec<usize>> {
     let validated = updates.iter().map(|update_entry| {
-        let is_valid = rules.iter().all(|r| r.test_compliance(update_entry));
+        let is_valid = update_entry.validate(rules).is_valid();
         (is_valid, update_entry)
     });
     
     let valids = validated.clone().filter(|(x,_)| *x);
-    let valid_result = valids.clone()
+    valids.clone()
         .map(|(_,y)| y)
         .cloned()
-        .collect::<Vec<_>>();
-
-    valid_result
+        .collect::<Vec<_>>()
 }
 
+// The rules from the puzzle description, shared by the tests.
 #[cfg(test)]
-mod tests{
-    use super::*;
-
-    #[test]
-    fn content_parse() {
-        let content = 
-"1|2
-3|4
-5|6
-
-1,2,3,4,5
-5,4,3,2,1";
-
-        let (rule, updates) = parse_content(content).unwrap();
-
-        assert_eq!(rule, vec![Rule::new(1, 2), Rule::new(3, 4), Rule::new(5, 6)]);
-        assert_eq!(updates, vec![vec![1,2,3,4,5], vec![5,4,3,2,1]]);
-    }
-
-    #[test]
-    fn task1_test() {
-        let content = 
+pub const EXAMPLE_RULES: &str =
 "47|53
 97|13
 97|61
@@ -122,17 +215,40 @@ mod tests{
 75|61
 47|29
 75|13
-53|13
+53|13";
+
+#[cfg(test)]
+mod tests{
+    use super::*;
 
-75,47,61,53,29
+    #[test]
+    fn content_parse() {
+        let content = 
+"1|2
+3|4
+5|6
+
+1,2,3,4,5
+5,4,3,2,1";
+
+        let (rule, updates) = parse_input(content).unwrap();
+
+        assert_eq!(rule, vec![Rule::new(1, 2), Rule::new(3, 4), Rule::new(5, 6)]);
+        assert_eq!(updates, vec![vec![1,2,3,4,5], vec![5,4,3,2,1]]);
+    }
+
+    #[test]
+    fn task1_test() {
+        let content = format!("{}\n\n{}", EXAMPLE_RULES,
+"75,47,61,53,29
 97,61,53,29,13
 75,29,13
 75,97,47,61,53
 61,13,29
-97,13,75,29,47";
+97,13,75,29,47");
 
-        let (rules, updates) = parse_content(content).unwrap();
-        let valid_result = task_1(&rules, &updates);
+        let (rules, updates) = parse_input(&content).unwrap();
+        let valid_result = task_1(&IndexedRules::new(&rules), &updates);
 
         
         assert_eq!(valid_result.iter().map(get_middle).sum::<usize>(), 143);
@@ -141,39 +257,77 @@ mod tests{
 
     #[test]
     fn task2_test() {
-        let content = 
-"47|53
-97|13
-97|61
-97|47
-75|29
-61|13
-75|53
-29|13
-97|29
-53|29
-61|53
-97|53
-61|29
-47|13
-75|47
-97|75
-47|61
-75|61
-47|29
-75|13
-53|13
-
-75,97,47,61,53
+        let content = format!("{}\n\n{}", EXAMPLE_RULES,
+"75,97,47,61,53
 61,13,29
-97,13,75,29,47";
+97,13,75,29,47");
 
-        let (rules, updates) = parse_content(content).unwrap();
-        let valid_result = task_2(&rules, &updates);
+        let (rules, updates) = parse_input(&content).unwrap();
+        let (valid_result, _) = task_2(&IndexedRules::new(&rules), &updates);
 
         
         assert_eq!(valid_result.iter().map(get_middle).sum::<usize>(), 123);
         assert_eq!(valid_result.len(), 3);
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    fn task2_skips_cycles() {
+        let content =
+"1|2
+2|3
+3|1
+4|5
+
+3,2,1
+5,4,6";
+
+        let (rules, updates) = parse_input(content).unwrap();
+
+        let (solved, skipped) = task_2(&IndexedRules::new(&rules), &updates);
+
+        assert_eq!(solved, vec![vec![4, 6, 5]]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, &updates[0]);
+    }
+
+    #[test]
+    fn dot_example() {
+        let content = format!("{}\n\n{}", EXAMPLE_RULES,
+"75,47,61,53,29");
+
+        let (rules, _) = parse_input(&content).unwrap();
+
+        assert_eq!(RuleSet::new(&rules).to_dot(), "\
+digraph rules {
+    13;
+    29;
+    47;
+    53;
+    61;
+    75;
+    97;
+    47 -> 53;
+    97 -> 13;
+    97 -> 61;
+    97 -> 47;
+    75 -> 29;
+    61 -> 13;
+    75 -> 53;
+    29 -> 13;
+    97 -> 29;
+    53 -> 29;
+    61 -> 53;
+    97 -> 53;
+    61 -> 29;
+    47 -> 13;
+    75 -> 47;
+    97 -> 75;
+    47 -> 61;
+    75 -> 61;
+    47 -> 29;
+    75 -> 13;
+    53 -> 13;
+}
+");
+    }
+}
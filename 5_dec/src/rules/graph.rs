@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::rules::model::Rule;
+
+#[derive(PartialEq, Debug)]
+pub enum SortError {
+    Cycle(Vec<usize>),
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortError::Cycle(cycle) => write!(f, "rules form a cycle: {:?}", cycle),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum TopologicalOrder {
+    Unique(Vec<usize>),
+    // `choice` holds two pages that were free to be placed at the same time.
+    Ambiguous { order: Vec<usize>, choice: (usize, usize) },
+}
+
+impl TopologicalOrder {
+    pub fn into_order(self) -> Vec<usize> {
+        match self {
+            TopologicalOrder::Unique(order) => order,
+            TopologicalOrder::Ambiguous { order, .. } => order,
+        }
+    }
+}
+
+pub struct PrecedenceGraph {
+    successors: HashMap<usize, Vec<usize>>,
+}
+
+impl PrecedenceGraph {
+    pub fn new(rules: &[Rule]) -> Self {
+        let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for rule in rules {
+            successors.entry(rule.x).or_default().push(rule.y);
+        }
+        Self { successors }
+    }
+
+    pub fn successors(&self, page: usize) -> &[usize] {
+        self.successors.get(&page).map_or(&[], |s| s.as_slice())
+    }
+
+    // Kahn's algorithm on the subgraph induced by the pages of `update`.
+    pub fn sort(&self, update: &[usize]) -> Result<TopologicalOrder, SortError> {
+        let pages: HashSet<usize> = update.iter().copied().collect();
+        let mut in_degree: HashMap<usize, usize> = pages.iter().map(|&p| (p, 0)).collect();
+        for &page in &pages {
+            for y in self.successors(page).iter().filter(|y| pages.contains(y)) {
+                *in_degree.get_mut(y).unwrap() += 1;
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut ready: VecDeque<usize> = update.iter()
+            .copied()
+            .filter(|p| in_degree[p] == 0 && seen.insert(*p))
+            .collect();
+        let mut order = Vec::with_capacity(pages.len());
+        let mut choice = None;
+
+        while let Some(page) = ready.pop_front() {
+            if choice.is_none() && !ready.is_empty() {
+                choice = Some((page, ready[0]));
+            }
+            order.push(page);
+            for y in self.successors(page).iter().filter(|y| pages.contains(y)) {
+                let degree = in_degree.get_mut(y).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push_back(*y);
+                }
+            }
+        }
+
+        if order.len() < pages.len() {
+            return Err(SortError::Cycle(self.find_cycle(&pages, &in_degree)));
+        }
+
+        Ok(match choice {
+            Some(choice) => TopologicalOrder::Ambiguous { order, choice },
+            None => TopologicalOrder::Unique(order),
+        })
+    }
+
+    // Every page Kahn could not place still has a predecessor among the
+    // unplaced pages, so walking predecessors must eventually repeat a page.
+    fn find_cycle(&self, pages: &HashSet<usize>, in_degree: &HashMap<usize, usize>) -> Vec<usize> {
+        let remaining: HashSet<usize> = pages.iter().copied().filter(|p| in_degree[p] > 0).collect();
+        let mut predecessor: HashMap<usize, usize> = HashMap::new();
+        for &x in &remaining {
+            for &y in self.successors(x).iter().filter(|y| remaining.contains(y)) {
+                predecessor.entry(y).or_insert(x);
+            }
+        }
+
+        let mut walk = vec![*remaining.iter().min().unwrap()];
+        let mut visited: HashMap<usize, usize> = HashMap::from([(walk[0], 0)]);
+        loop {
+            let next = predecessor[walk.last().unwrap()];
+            if let Some(&start) = visited.get(&next) {
+                let mut cycle = walk.split_off(start);
+                cycle.reverse();
+                return cycle;
+            }
+            visited.insert(next, walk.len());
+            walk.push(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_unique() {
+        let graph = PrecedenceGraph::new(&[Rule::new(1, 2), Rule::new(2, 3), Rule::new(1, 3), Rule::new(3, 7)]);
+
+        assert_eq!(graph.sort(&[3, 1, 2]), Ok(TopologicalOrder::Unique(vec![1, 2, 3])));
+    }
+
+    #[test]
+    fn test_sort_ambiguous() {
+        let graph = PrecedenceGraph::new(&[Rule::new(1, 2), Rule::new(1, 3)]);
+
+        assert_eq!(
+            graph.sort(&[3, 2, 1]),
+            Ok(TopologicalOrder::Ambiguous { order: vec![1, 2, 3], choice: (2, 3) })
+        );
+    }
+
+    #[test]
+    fn test_sort_cycle() {
+        let graph = PrecedenceGraph::new(&[Rule::new(1, 2), Rule::new(2, 3), Rule::new(3, 4), Rule::new(4, 2), Rule::new(4, 5)]);
+
+        match graph.sort(&[5, 4, 3, 2, 1]) {
+            Err(SortError::Cycle(cycle)) => {
+                assert_eq!(cycle.len(), 3);
+                let start = cycle.iter().position(|&p| p == 2).unwrap();
+                let rotated: Vec<usize> = cycle.iter().cycle().skip(start).take(3).copied().collect();
+                assert_eq!(rotated, vec![2, 3, 4]);
+            }
+            other => panic!("Expected a cycle, got {:?}", other),
+        }
+    }
+}
@@ -1,46 +1,42 @@
+use crate::error::ParseErrorKind;
 use crate::rules::model::Rule;
 
-pub trait RuleParser {
-    fn parse_rules(&self) -> Option<Vec<Rule>>;
-    fn parse_rule(&self) -> Option<Rule>;
-}
+// Parses `x|y`, reporting the 1-based column of the first offending character.
+pub fn parse_rule_line(line: &str) -> Result<Rule, (usize, ParseErrorKind)> {
+    let malformed = |column: usize, reason: &str| (column + 1, ParseErrorKind::MalformedRule(reason.to_string()));
+    let bytes = line.as_bytes();
+    let mut pos = 0;
 
-impl RuleParser for &str {
-    fn parse_rules(&self) -> Option<Vec<Rule>> {
-        let rule_candidates = self.lines().map(|x: &str| x.parse_rule());
-        
-        let mut rules: Vec<Rule> = vec![];
-        for maybe_rule in rule_candidates {
-            match maybe_rule {
-                None => return None,
-                Some(r) => rules.push(r)
-                
-            } 
+    let page = |pos: &mut usize| -> Result<usize, (usize, ParseErrorKind)> {
+        while *pos < bytes.len() && bytes[*pos] == b' ' {
+            *pos += 1;
+        }
+        let start = *pos;
+        while *pos < bytes.len() && bytes[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+        let number = line[start..*pos].parse().map_err(|_| malformed(start, "expected a page number"))?;
+        while *pos < bytes.len() && bytes[*pos] == b' ' {
+            *pos += 1;
         }
+        Ok(number)
+    };
 
-        Some(rules)
+    let x = page(&mut pos)?;
+    if bytes.get(pos) != Some(&b'|') {
+        return Err(malformed(pos, "expected '|'"));
     }
-
-    fn parse_rule(&self) -> Option<Rule> {
-        let mut x_y = self
-            .split("|")
-            .map(|number| number.trim().parse::<usize>());
-
-        let x = match x_y.next(){
-            Some(Ok(x)) => x,
-            _ => return None
-        };
-        let y = match x_y.next(){
-            Some(Ok(y)) => y,
-            _ => return None
-        };
-        
-        // Should be empty now!
-        assert_eq!(x_y.next(), None);
-
-        Some(Rule::new(x, y))
+    pos += 1;
+    let y = page(&mut pos)?;
+    if pos < bytes.len() {
+        return Err(malformed(pos, "expected end of line"));
     }
-} 
+    if x == y {
+        return Err((1, ParseErrorKind::SelfReferentialRule(x)));
+    }
+
+    Ok(Rule::new(x, y))
+}
 
 
 #[cfg(test)]
@@ -49,24 +45,13 @@ mod tests {
 
     #[test]
     fn test_parse_rule() {
-        let rule = "1|2".parse_rule().unwrap();
-        assert_eq!(rule, Rule::new(1, 2));
+        assert_eq!(parse_rule_line("1|2"), Ok(Rule::new(1, 2)));
+        assert_eq!(parse_rule_line(" 1 | 2 "), Ok(Rule::new(1, 2)));
     }
 
     #[test]
-    fn test_parse_rules() {
-        let input = format!("{}\n{}\n{}", "1|2", "3|4", "5|6");
-        
-        match input.as_str().parse_rules(){
-            None => panic!("Failed to parse rules"),
-            Some(rules) => {
-                assert_eq!(rules.len(), 3, "Input should have 3 rules, but found {}", rules.len());
-                assert_eq!(rules[0], Rule::new(1, 2), "First rule should be 1|2, but found {:?}", rules[0]);
-                assert_eq!(rules[1], Rule::new(3, 4), "Second rule should be 3|4, but found {:?}", rules[1]);
-                assert_eq!(rules[2], Rule::new(5, 6), "Third rule should be 5|6, but found {:?}", rules[2]);
-            }
-        }
+    fn test_parse_rule_rejects() {
+        assert_eq!(parse_rule_line("1|2|3"), Err((4, ParseErrorKind::MalformedRule("expected end of line".to_string()))));
+        assert_eq!(parse_rule_line("2|2"), Err((1, ParseErrorKind::SelfReferentialRule(2))));
     }
-
-    
-}
\ No newline at end of file
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::rules::model::Rule;
+
+// The page-ordering rules as a directed graph, for questions that span more
+// than a single update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    successors: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl RuleSet {
+    pub fn new(rules: &[Rule]) -> Self {
+        let mut unique = Vec::with_capacity(rules.len());
+        let mut successors: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for rule in rules {
+            successors.entry(rule.y).or_default();
+            if successors.entry(rule.x).or_default().insert(rule.y) {
+                unique.push(rule.clone());
+            }
+        }
+        Self { rules: unique, successors }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.successors.keys().copied()
+    }
+
+    // Pages reachable from `page` by one or more rules, optionally ignoring a
+    // single direct rule.
+    fn reachable(&self, page: usize, skip: Option<&Rule>) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([page]);
+        while let Some(current) = queue.pop_front() {
+            for &next in self.successors.get(&current).into_iter().flatten() {
+                if skip.is_some_and(|rule| rule.x == current && rule.y == next) {
+                    continue;
+                }
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn must_precede(&self, a: usize, b: usize) -> bool {
+        self.reachable(a, None).contains(&b)
+    }
+
+    // Pairs `(a, b)` with `a < b` where neither page has to come first.
+    pub fn unconstrained(&self) -> Vec<(usize, usize)> {
+        let closure: BTreeMap<usize, BTreeSet<usize>> = self.pages()
+            .map(|page| (page, self.reachable(page, None)))
+            .collect();
+
+        let pages: Vec<usize> = self.pages().collect();
+        pages.iter().enumerate()
+            .flat_map(|(i, &a)| pages[i + 1..].iter().map(move |&b| (a, b)))
+            .filter(|(a, b)| !closure[a].contains(b) && !closure[b].contains(a))
+            .collect()
+    }
+
+    // Rules whose ordering already follows from a longer chain of rules.
+    pub fn redundant(&self) -> Vec<Rule> {
+        self.rules.iter()
+            .filter(|rule| self.reachable(rule.x, Some(rule)).contains(&rule.y))
+            .cloned()
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph rules {\n");
+        for page in self.pages() {
+            dot.push_str(&format!("    {};\n", page));
+        }
+        for rule in &self.rules {
+            dot.push_str(&format!("    {} -> {};\n", rule.x, rule.y));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // Drops implied rules one at a time. For acyclic rules this is the unique
+    // transitive reduction; with cycles it is a minimal, not necessarily
+    // minimum, equivalent rule set.
+    pub fn transitive_reduction(&self) -> RuleSet {
+        let mut reduced = self.clone();
+        for rule in &self.rules {
+            if reduced.reachable(rule.x, Some(rule)).contains(&rule.y) {
+                reduced.successors.get_mut(&rule.x).unwrap().remove(&rule.y);
+                reduced.rules.retain(|r| r != rule);
+            }
+        }
+        reduced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule_set(pairs: &[(usize, usize)]) -> RuleSet {
+        let rules: Vec<Rule> = pairs.iter().map(|&(x, y)| Rule::new(x, y)).collect();
+        RuleSet::new(&rules)
+    }
+
+    #[test]
+    fn test_must_precede() {
+        let rules = rule_set(&[(1, 2), (2, 3), (4, 3)]);
+
+        assert!(rules.must_precede(1, 3));
+        assert!(!rules.must_precede(3, 1));
+        assert!(!rules.must_precede(1, 4));
+    }
+
+    #[test]
+    fn test_unconstrained() {
+        let rules = rule_set(&[(1, 2), (2, 3), (4, 3)]);
+
+        assert_eq!(rules.unconstrained(), vec![(1, 4), (2, 4)]);
+    }
+
+    #[test]
+    fn test_redundant() {
+        let rules = rule_set(&[(1, 2), (2, 3), (1, 3), (3, 4), (1, 4)]);
+
+        assert_eq!(rules.redundant(), vec![Rule::new(1, 3), Rule::new(1, 4)]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let rules = rule_set(&[(47, 53), (97, 13), (97, 47)]);
+
+        assert_eq!(rules.to_dot(), "\
+digraph rules {
+    13;
+    47;
+    53;
+    97;
+    47 -> 53;
+    97 -> 13;
+    97 -> 47;
+}
+");
+    }
+
+    #[test]
+    fn test_transitive_reduction() {
+        let rules = rule_set(&[(1, 2), (2, 3), (1, 3), (3, 4), (1, 4), (1, 2)]);
+        let reduced = rules.transitive_reduction();
+
+        assert_eq!(reduced.rules(), &[Rule::new(1, 2), Rule::new(2, 3), Rule::new(3, 4)]);
+        assert!(reduced.redundant().is_empty());
+        for a in 1..=4 {
+            for b in 1..=4 {
+                assert_eq!(reduced.must_precede(a, b), rules.must_precede(a, b));
+            }
+        }
+    }
+
+    #[test]
+    fn test_transitive_reduction_with_cycle() {
+        let rules = rule_set(&[(1, 2), (2, 3), (3, 1), (1, 3)]);
+        let reduced = rules.transitive_reduction();
+
+        assert_eq!(reduced.rules().len(), 3);
+        assert!(reduced.must_precede(3, 2));
+    }
+}
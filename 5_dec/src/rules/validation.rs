@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use crate::rules::model::Rule;
 
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum RuleIndex{
     None,
     X(usize),
@@ -24,27 +26,87 @@ impl RuleIndexer for Vec<usize> {
     }
 }
 
-impl Rule {
+// Position of every page in an update, so a rule can be looked up in O(1)
+// instead of scanning the update twice.
+pub struct PageIndex {
+    positions: HashMap<usize, usize>,
+}
 
-    pub fn test_compliance(&self, updates: &Vec<usize>) -> bool {
-        match updates.rule_indexes(self) {
-            RuleIndex::Both(x_i, y_i) if x_i > y_i => false,
-            _ => true
+impl PageIndex {
+    pub fn new(update: &[usize]) -> Self {
+        let mut positions = HashMap::with_capacity(update.len());
+        for (i, &page) in update.iter().enumerate() {
+            positions.entry(page).or_insert(i);
         }
+        Self { positions }
     }
 
-    // pub fn try_find_index(&self, updates: &Vec<usize>) -> bool {
-    //     match updates.rule_indexes(self) {
-    //         RuleIndex::Both(x_i, y_i) if x_i < y_i => true,
-    //         _ => false
-    //     }
-    // }
+    pub fn position(&self, page: usize) -> Option<usize> {
+        self.positions.get(&page).copied()
+    }
+}
+
+impl RuleIndexer for PageIndex {
+    fn rule_indexes(&self, rule: &Rule) -> RuleIndex {
+        match (self.positions.get(&rule.x), self.positions.get(&rule.y)) {
+            (Some(&x_i), Some(&y_i)) => RuleIndex::Both(x_i, y_i),
+            (Some(&x_i), None) => RuleIndex::X(x_i),
+            (None, Some(&y_i)) => RuleIndex::Y(y_i),
+            _ => RuleIndex::None
+        }
+    }
 }
 
+// Rules grouped by their `x` page. Only rules starting at a page of the
+// update are ever looked at.
+pub struct IndexedRules<'a> {
+    rules: &'a [Rule],
+    by_x: HashMap<usize, Vec<usize>>,
+}
+
+impl<'a> IndexedRules<'a> {
+    pub fn new(rules: &'a [Rule]) -> Self {
+        let mut by_x: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, rule) in rules.iter().enumerate() {
+            by_x.entry(rule.x).or_default().push(i);
+        }
+        Self { rules, by_x }
+    }
+
+    // Rules with both pages in the update, in their original order.
+    pub fn active(&self, index: &PageIndex) -> Vec<(&'a Rule, RuleIndex)> {
+        let mut active: Vec<(usize, RuleIndex)> = index.positions.keys()
+            .filter_map(|page| self.by_x.get(page))
+            .flatten()
+            .filter_map(|&i| match index.rule_indexes(&self.rules[i]) {
+                RuleIndex::Both(x_i, y_i) => Some((i, RuleIndex::Both(x_i, y_i))),
+                _ => None,
+            })
+            .collect();
+        active.sort_by_key(|(i, _)| *i);
+
+        active.into_iter().map(|(i, rule_index)| (&self.rules[i], rule_index)).collect()
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::updates::model::UpdateValidator;
+
+    #[test]
+    fn test_rule_complies() {
+        let rule = [Rule::new(1, 2)];
+        let updates = vec![1, 2, 3];
+        assert!(updates.validate(&IndexedRules::new(&rule)).is_valid());
+    }
+    
+    #[test]
+    fn test_rule_not_complies() {
+        let rule = [Rule::new(3, 2)];
+        let updates = vec![1, 2, 3];
+        assert!(!updates.validate(&IndexedRules::new(&rule)).is_valid());
+    }
 
     #[test]
     fn test_rule_index() {
@@ -53,17 +115,28 @@ mod tests {
         assert_eq!(updates.rule_indexes(&rule), RuleIndex::Both(2, 1));
     }
 
+
     #[test]
-    fn test_rule_complies() {
-        let rule = Rule::new(1, 2);
-        let updates = vec![1, 2, 3];
-        assert!(rule.test_compliance(&updates));
+    fn test_page_index() {
+        let index = PageIndex::new(&[1, 2, 3]);
+        assert_eq!(index.rule_indexes(&Rule::new(3, 2)), RuleIndex::Both(2, 1));
+        assert_eq!(index.rule_indexes(&Rule::new(3, 7)), RuleIndex::X(2));
+        assert_eq!(index.rule_indexes(&Rule::new(7, 1)), RuleIndex::Y(0));
+        assert_eq!(index.rule_indexes(&Rule::new(7, 8)), RuleIndex::None);
+        assert_eq!(index.position(3), Some(2));
+        assert_eq!(index.position(7), None);
     }
-    
+
     #[test]
-    fn test_rule_not_complies() {
-        let rule = Rule::new(3, 2);
-        let updates = vec![1, 2, 3];
-        assert!(!rule.test_compliance(&updates));
+    fn test_indexed_rules_active() {
+        let rules = vec![Rule::new(3, 2), Rule::new(1, 7), Rule::new(1, 2), Rule::new(7, 8)];
+        let indexed = IndexedRules::new(&rules);
+
+        let active = indexed.active(&PageIndex::new(&[1, 2, 3]));
+
+        assert_eq!(active, vec![
+            (&rules[0], RuleIndex::Both(2, 1)),
+            (&rules[2], RuleIndex::Both(0, 1)),
+        ]);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,62 @@
+use crate::rules::set::RuleSet;
+use crate::rules::validation::{PageIndex, RuleIndex, RuleIndexer};
+use crate::updates::model::Updates;
+
+pub trait UpdateGraph {
+    fn to_dot(&self, rules: &RuleSet) -> String;
+}
+
+// The whole rule graph with the pages of the update filled in and labelled
+// with their index. Rules between two update pages are drawn bold, in red
+// when the update breaks them; all other rules are greyed out.
+impl UpdateGraph for Updates {
+    fn to_dot(&self, rules: &RuleSet) -> String {
+        let index = PageIndex::new(self);
+        let mut dot = String::from("digraph update {\n    node [style=filled, fillcolor=white, color=gray];\n");
+
+        for page in rules.pages() {
+            match index.position(page) {
+                Some(i) => dot.push_str(&format!("    {} [label=\"{} ({})\", fillcolor=lightblue, color=black];\n", page, page, i)),
+                None => dot.push_str(&format!("    {};\n", page)),
+            }
+        }
+        for rule in rules.rules() {
+            let style = match index.rule_indexes(rule) {
+                RuleIndex::Both(x, y) if x < y => " [penwidth=2]",
+                RuleIndex::Both(_, _) => " [color=red, penwidth=2]",
+                _ => " [color=gray]",
+            };
+            dot.push_str(&format!("    {} -> {}{};\n", rule.x, rule.y, style));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::model::Rule;
+
+    #[test]
+    fn test_update_to_dot() {
+        let rules = RuleSet::new(&[Rule::new(97, 75), Rule::new(75, 47), Rule::new(47, 13), Rule::new(61, 13)]);
+        let update: Updates = vec![75, 97, 47];
+
+        assert_eq!(update.to_dot(&rules), "\
+digraph update {
+    node [style=filled, fillcolor=white, color=gray];
+    13;
+    47 [label=\"47 (2)\", fillcolor=lightblue, color=black];
+    61;
+    75 [label=\"75 (0)\", fillcolor=lightblue, color=black];
+    97 [label=\"97 (1)\", fillcolor=lightblue, color=black];
+    97 -> 75 [color=red, penwidth=2];
+    75 -> 47 [penwidth=2];
+    47 -> 13 [color=gray];
+    61 -> 13 [color=gray];
+}
+");
+    }
+}
@@ -1,25 +1,37 @@
+use crate::rules::graph::{PrecedenceGraph, SortError};
 use crate::rules::model::Rule;
-use crate::rules::validation::{RuleIndex, RuleIndexer};
-use std::collections::{HashMap, HashSet};
+use crate::rules::validation::{IndexedRules, PageIndex, RuleIndex};
+use std::fmt;
 
 pub type Updates = Vec<usize>;
 
 #[derive(PartialEq, Debug)]
-enum ValidationSample {
+pub enum ValidationSample {
     Valid((Rule, RuleIndex)),
     Invalid((Rule, RuleIndex)),
 }
 
+#[derive(PartialEq, Debug)]
+pub struct Violation {
+    pub rule: Rule,
+    pub x_index: usize,
+    pub y_index: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}|{} violated: {} at index {}, {} at index {}",
+            self.rule.x, self.rule.y, self.rule.x, self.x_index, self.rule.y, self.y_index)
+    }
+}
+
 pub struct UpdateValidationResult {
     rule_map: Vec<ValidationSample>
 }
 
 impl UpdateValidationResult {
     pub fn is_valid(&self) -> bool {
-        self.rule_map.iter().all(|v| match v {
-            ValidationSample::Valid(_) => true,
-            _ => false
-        })
+        self.rule_map.iter().all(|v| matches!(v, ValidationSample::Valid(_)))
     }
 
     pub fn rules(&self) -> Vec<Rule> {
@@ -29,74 +41,54 @@ impl UpdateValidationResult {
         }).collect()
     }
 
+    #[allow(dead_code)]
     pub fn valid_iter(&self) -> impl Iterator<Item = &ValidationSample> {
-        self.rule_map.iter().filter(|v| match v {
-            ValidationSample::Valid(_) => true,
-            _ => false
-        })
+        self.rule_map.iter().filter(|v| matches!(v, ValidationSample::Valid(_)))
     }
 
+    #[allow(dead_code)]
     pub fn invalid_iter(&self) -> impl Iterator<Item = &ValidationSample> {
-        self.rule_map.iter().filter(|v| match v {
-            ValidationSample::Invalid(_) => true,
-            _ => false
-        })
+        self.rule_map.iter().filter(|v| matches!(v, ValidationSample::Invalid(_)))
+    }
+
+    pub fn explain(&self) -> Vec<Violation> {
+        self.rule_map.iter().filter_map(|v| match v {
+            ValidationSample::Invalid((rule, RuleIndex::Both(x_index, y_index))) =>
+                Some(Violation { rule: rule.clone(), x_index: *x_index, y_index: *y_index }),
+            _ => None
+        }).collect()
     }
 }
 
 pub trait UpdateValidator {
-    fn validate(&self, rules: &Vec<Rule>) -> UpdateValidationResult;
+    fn validate(&self, rules: &IndexedRules) -> UpdateValidationResult;
 }
 
 impl UpdateValidator for Updates {
-    fn validate(&self, rules: &Vec<Rule>) -> UpdateValidationResult {
-        let mut rule_map = Vec::new();
-
-        for r in rules {
-            match self.rule_indexes(r){
-                RuleIndex::Both(x, y) => {
-                    rule_map.push(match x < y {
-                        true => ValidationSample::Valid((r.clone(), RuleIndex::Both(x, y))),
-                        false => ValidationSample::Invalid((r.clone(), RuleIndex::Both(x, y)))
-                    });
-                },
-                _ => continue
-            }
-        }
+    fn validate(&self, rules: &IndexedRules) -> UpdateValidationResult {
+        let rule_map = rules.active(&PageIndex::new(self))
+            .into_iter()
+            .map(|(r, rule_index)| match rule_index {
+                RuleIndex::Both(x, y) if x < y => ValidationSample::Valid((r.clone(), rule_index)),
+                _ => ValidationSample::Invalid((r.clone(), rule_index)),
+            })
+            .collect();
 
         UpdateValidationResult { rule_map }
     }
 }
 
 pub trait RuleSolver {
-    fn solve(&self, rules: &Vec<Rule>) -> Vec<usize>;
+    fn solve(&self, rules: &IndexedRules) -> Result<Vec<usize>, SortError>;
 }
 
 impl RuleSolver for Updates {
-    fn solve(&self, rules: &Vec<Rule>) -> Vec<usize> {
-        let mut solved: Vec<usize> = Vec::new();
-         
-        let mut rules = self.validate(rules).rules();
-        let ys: HashSet<usize> = rules.iter().map(|r| r.y).collect();
-        while rules.len() > 0 {
-            let xs: HashSet<usize> = rules.iter().map(|r| r.x).collect();
-        
-            let y_counts: HashMap<usize, usize> = xs.iter().map(|x| {
-                let count = rules.iter().filter(|r| r.y == *x).count();
-                (*x, count)
-            }).collect();
-            
-            let x_only= *y_counts.iter().find(|(_, count)| **count==0).unwrap().0;
-            solved.push(x_only);
-            rules = rules.iter().filter(|r| r.x != x_only).cloned().collect();
-        }
-        
-        let solved_set: HashSet<usize> = solved.iter().cloned().collect();
+    fn solve(&self, rules: &IndexedRules) -> Result<Vec<usize>, SortError> {
+        let active_rules = self.validate(rules).rules();
 
-        let remaining_y = ys.difference(&solved_set).next().unwrap();
-        solved.push(*remaining_y);
-        
-        solved
+        PrecedenceGraph::new(&active_rules)
+            .sort(self)
+            .map(|order| order.into_order())
     }
 }
 
@@ -107,12 +99,12 @@ mod tests {
     #[test]
     fn test_update_validator() {
         let updates = vec![1, 2, 3];
-        let rule = &vec![
+        let rule = &[
             Rule::new(1, 2),
             Rule::new(3, 2),
         ];
 
-        let result = updates.validate(rule);
+        let result = updates.validate(&IndexedRules::new(rule));
 
         let actual = result.valid_iter().next().unwrap();
         let expected = ValidationSample::Valid((Rule::new(1, 2), RuleIndex::Both(0, 1)));
@@ -122,12 +114,12 @@ mod tests {
     #[test]
     fn test_validation_result() {
         let updates = vec![1, 2, 3];
-        let rule = &vec![
+        let rule = &[
             Rule::new(1, 2),
             Rule::new(3, 2),
         ];
 
-        let result = updates.validate(rule);
+        let result = updates.validate(&IndexedRules::new(rule));
 
         assert!(!result.is_valid());
     }
@@ -136,23 +128,38 @@ mod tests {
     #[test]
     fn test_get_active_rules() {
         let updates = vec![1, 2, 3];
-        let rule = &vec![
+        let rule = &[
             Rule::new(1, 2),
             Rule::new(3, 2),
             Rule::new(0, 2),
             Rule::new(3, 7),
         ];
 
-        let result = updates.validate(rule).rules();
+        let result = updates.validate(&IndexedRules::new(rule)).rules();
 
         assert!(result.contains(&Rule::new(1, 2)));
         assert!(result.contains(&Rule::new(3, 2)));
     }
 
+    #[test]
+    fn test_explain() {
+        let updates = vec![75, 97, 47, 61, 53];
+        let rules = &[
+            Rule::new(97, 75),
+            Rule::new(75, 47),
+            Rule::new(97, 61),
+        ];
+
+        let violations = updates.validate(&IndexedRules::new(rules)).explain();
+
+        assert_eq!(violations, vec![Violation { rule: Rule::new(97, 75), x_index: 1, y_index: 0 }]);
+        assert_eq!(violations[0].to_string(), "97|75 violated: 97 at index 1, 75 at index 0");
+    }
+
     #[test]
     fn test_rules_solver() {
         let updates = vec![1, 2, 3, 4, 5];
-        let rules = &vec![
+        let rules = &[
             Rule::new(1, 2),
             Rule::new(1, 3),
             Rule::new(3, 2),
@@ -161,8 +168,77 @@ mod tests {
             Rule::new(4, 5),
         ];
 
-        let result = updates.solve(&rules);
+        let result = updates.solve(&IndexedRules::new(rules)).unwrap();
 
         assert_eq!(result, vec![1, 3, 2, 4, 5]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rules_solver_cycle() {
+        let updates = vec![1, 2, 3];
+        let rules = &[
+            Rule::new(1, 2),
+            Rule::new(2, 3),
+            Rule::new(3, 1),
+        ];
+
+        assert!(matches!(updates.solve(&IndexedRules::new(rules)), Err(SortError::Cycle(cycle)) if cycle.len() == 3));
+    }
+
+    // Generates 100k acyclic rules over 2000 pages and compares the indexed
+    // validator against a plain scan of every rule. Run with
+    // `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_validate_100k_rules() {
+        use crate::rules::validation::RuleIndexer;
+        use std::time::Instant;
+
+        let mut seed: u64 = 0x5eed;
+        let mut next = move |bound: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+
+        let pages = 2000;
+        let mut rules = Vec::with_capacity(100_000);
+        while rules.len() < 100_000 {
+            let (a, b) = (next(pages), next(pages));
+            if a != b {
+                rules.push(Rule::new(a.min(b), a.max(b)));
+            }
+        }
+        let updates: Vec<Updates> = (0..1000)
+            .map(|_| (0..23).map(|_| next(pages)).collect())
+            .collect();
+
+        let start = Instant::now();
+        let naive: Vec<Vec<(Rule, RuleIndex)>> = updates.iter()
+            .map(|update| rules.iter()
+                .filter_map(|r| match update.rule_indexes(r) {
+                    RuleIndex::Both(x, y) => Some((r.clone(), RuleIndex::Both(x, y))),
+                    _ => None,
+                })
+                .collect())
+            .collect();
+        let naive_time = start.elapsed();
+
+        let start = Instant::now();
+        let indexed_rules = IndexedRules::new(&rules);
+        let indexed: Vec<UpdateValidationResult> = updates.iter()
+            .map(|update| update.validate(&indexed_rules))
+            .collect();
+        let indexed_time = start.elapsed();
+
+        for (expected, actual) in naive.into_iter().zip(indexed.iter()) {
+            let actual: Vec<(Rule, RuleIndex)> = actual.rule_map.iter()
+                .map(|sample| match sample {
+                    ValidationSample::Valid((r, i)) | ValidationSample::Invalid((r, i)) => (r.clone(), i.clone()),
+                })
+                .collect();
+            assert_eq!(actual, expected);
+        }
+        println!("naive: {:?}, indexed: {:?}", naive_time, indexed_time);
+        assert!(indexed_time < naive_time);
+    }
+}
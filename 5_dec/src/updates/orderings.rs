@@ -0,0 +1,209 @@
+use std::fmt;
+
+use crate::rules::graph::{PrecedenceGraph, SortError, TopologicalOrder};
+use crate::rules::model::Rule;
+use crate::rules::validation::{PageIndex, RuleIndex, RuleIndexer};
+use crate::updates::model::Updates;
+
+// `count_orderings` keeps one counter per subset of pages.
+pub const MAX_COUNTED_PAGES: usize = 20;
+
+#[derive(PartialEq, Debug)]
+pub enum OrderingError {
+    TooLarge { pages: usize, limit: usize },
+}
+
+impl fmt::Display for OrderingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderingError::TooLarge { pages, limit } =>
+                write!(f, "cannot count orderings of {} pages, the limit is {}", pages, limit),
+        }
+    }
+}
+
+// For every position in the update, the positions that have to come after it.
+fn successors(update: &[usize], rules: &[Rule]) -> Vec<Vec<usize>> {
+    let index = PageIndex::new(update);
+    let mut successors = vec![Vec::new(); update.len()];
+    for rule in rules {
+        if let RuleIndex::Both(x, y) = index.rule_indexes(rule) {
+            successors[x].push(y);
+        }
+    }
+    successors
+}
+
+pub trait UpdateOrderings {
+    fn count_orderings(&self, rules: &[Rule]) -> Result<u64, OrderingError>;
+    fn orderings(&self, rules: &[Rule]) -> Orderings;
+    fn has_unique_ordering(&self, graph: &PrecedenceGraph) -> Result<bool, SortError>;
+}
+
+impl UpdateOrderings for Updates {
+    // Counts linear extensions with a DP over the set of pages placed so far.
+    fn count_orderings(&self, rules: &[Rule]) -> Result<u64, OrderingError> {
+        let n = self.len();
+        if n > MAX_COUNTED_PAGES {
+            return Err(OrderingError::TooLarge { pages: n, limit: MAX_COUNTED_PAGES });
+        }
+
+        let mut must_follow = vec![0usize; n];
+        for (x, ys) in successors(self, rules).into_iter().enumerate() {
+            for y in ys {
+                must_follow[y] |= 1 << x;
+            }
+        }
+
+        let mut count = vec![0u64; 1 << n];
+        count[0] = 1;
+        for placed in 0..(1usize << n) {
+            if count[placed] == 0 {
+                continue;
+            }
+            for i in (0..n).filter(|&i| placed & (1 << i) == 0 && must_follow[i] & !placed == 0) {
+                count[placed | (1 << i)] += count[placed];
+            }
+        }
+        Ok(count[(1 << n) - 1])
+    }
+
+    fn orderings(&self, rules: &[Rule]) -> Orderings {
+        Orderings::new(self.clone(), successors(self, rules))
+    }
+
+    fn has_unique_ordering(&self, graph: &PrecedenceGraph) -> Result<bool, SortError> {
+        graph.sort(self).map(|order| matches!(order, TopologicalOrder::Unique(_)))
+    }
+}
+
+// Enumerates linear extensions by backtracking; `cursors[d]` is the next
+// position to try at depth `d`.
+pub struct Orderings {
+    pages: Vec<usize>,
+    successors: Vec<Vec<usize>>,
+    in_degree: Vec<usize>,
+    placed: Vec<bool>,
+    order: Vec<usize>,
+    cursors: Vec<usize>,
+    done: bool,
+}
+
+impl Orderings {
+    fn new(pages: Vec<usize>, successors: Vec<Vec<usize>>) -> Self {
+        let n = pages.len();
+        let mut in_degree = vec![0; n];
+        for &y in successors.iter().flatten() {
+            in_degree[y] += 1;
+        }
+        Self { pages, successors, in_degree, placed: vec![false; n], order: Vec::with_capacity(n), cursors: vec![0; n + 1], done: false }
+    }
+
+    fn place(&mut self, i: usize) {
+        self.placed[i] = true;
+        self.order.push(i);
+        for &y in &self.successors[i] {
+            self.in_degree[y] -= 1;
+        }
+    }
+
+    fn unplace(&mut self) {
+        let i = self.order.pop().unwrap();
+        self.placed[i] = false;
+        for &y in &self.successors[i] {
+            self.in_degree[y] += 1;
+        }
+    }
+}
+
+impl Iterator for Orderings {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        let n = self.pages.len();
+        if self.done {
+            return None;
+        }
+        if n == 0 {
+            self.done = true;
+            return Some(vec![]);
+        }
+        if self.order.len() == n {
+            self.unplace();
+        }
+
+        loop {
+            let depth = self.order.len();
+            let candidate = (self.cursors[depth]..n).find(|&i| !self.placed[i] && self.in_degree[i] == 0);
+            match candidate {
+                Some(i) => {
+                    self.cursors[depth] = i + 1;
+                    self.place(i);
+                    if self.order.len() == n {
+                        return Some(self.order.iter().map(|&i| self.pages[i]).collect());
+                    }
+                    self.cursors[depth + 1] = 0;
+                },
+                None if depth == 0 => {
+                    self.done = true;
+                    return None;
+                },
+                None => self.unplace(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_orderings() {
+        let update = vec![1, 2, 3, 4];
+
+        assert_eq!(update.count_orderings(&[]), Ok(24));
+        assert_eq!(update.count_orderings(&[Rule::new(1, 2)]), Ok(12));
+        assert_eq!(update.count_orderings(&[Rule::new(1, 2), Rule::new(2, 3), Rule::new(3, 4)]), Ok(1));
+        assert_eq!(update.count_orderings(&[Rule::new(1, 2), Rule::new(2, 1)]), Ok(0));
+    }
+
+    #[test]
+    fn test_count_orderings_limit() {
+        let update: Updates = (0..=MAX_COUNTED_PAGES).collect();
+
+        assert_eq!(
+            update.count_orderings(&[]),
+            Err(OrderingError::TooLarge { pages: MAX_COUNTED_PAGES + 1, limit: MAX_COUNTED_PAGES })
+        );
+    }
+
+    #[test]
+    fn test_orderings() {
+        let update = vec![3, 1, 2];
+        let rules = [Rule::new(1, 2), Rule::new(5, 1)];
+
+        let orderings: Vec<Vec<usize>> = update.orderings(&rules).collect();
+
+        assert_eq!(orderings, vec![vec![3, 1, 2], vec![1, 3, 2], vec![1, 2, 3]]);
+        assert_eq!(orderings.len() as u64, update.count_orderings(&rules).unwrap());
+    }
+
+    #[test]
+    fn test_orderings_match_count() {
+        let update = vec![1, 2, 3, 4, 5, 6];
+        let rules = [Rule::new(1, 4), Rule::new(2, 4), Rule::new(4, 6), Rule::new(3, 5)];
+
+        assert_eq!(update.orderings(&rules).count() as u64, update.count_orderings(&rules).unwrap());
+        assert_eq!(update.orderings(&[Rule::new(1, 2), Rule::new(2, 1)]).count(), 0);
+    }
+
+    #[test]
+    fn test_has_unique_ordering() {
+        let update = vec![3, 1, 2];
+
+        assert_eq!(update.has_unique_ordering(&PrecedenceGraph::new(&[Rule::new(1, 2), Rule::new(3, 1)])), Ok(true));
+        assert_eq!(update.has_unique_ordering(&PrecedenceGraph::new(&[Rule::new(1, 2)])), Ok(false));
+        assert!(update.has_unique_ordering(&PrecedenceGraph::new(&[Rule::new(1, 2), Rule::new(2, 1)])).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::rules::graph::{PrecedenceGraph, SortError, TopologicalOrder};
+use crate::rules::model::Rule;
+use crate::rules::validation::IndexedRules;
+use crate::updates::model::{UpdateValidator, Updates};
+
+// Beyond this many pages an ambiguous update is not repaired exactly, the
+// subset DP would need 2^n states.
+pub const MAX_EXACT_REPAIR: usize = 20;
+
+#[derive(PartialEq, Debug)]
+pub enum RepairError {
+    Cycle(Vec<usize>),
+    TooLarge { pages: usize, limit: usize },
+}
+
+impl fmt::Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepairError::Cycle(cycle) => write!(f, "rules form a cycle: {:?}", cycle),
+            RepairError::TooLarge { pages, limit } =>
+                write!(f, "ambiguous update of {} pages exceeds the exact repair limit of {}", pages, limit),
+        }
+    }
+}
+
+// Swaps the pages at `index` and `index + 1`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Swap {
+    pub index: usize,
+    pub pages: (usize, usize),
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Repair {
+    pub order: Vec<usize>,
+    pub swaps: Vec<Swap>,
+}
+
+pub trait UpdateRepairer {
+    fn repair_minimal(&self, rules: &IndexedRules) -> Result<Repair, RepairError>;
+}
+
+impl UpdateRepairer for Updates {
+    fn repair_minimal(&self, rules: &IndexedRules) -> Result<Repair, RepairError> {
+        let active_rules = self.validate(rules).rules();
+
+        let target = match PrecedenceGraph::new(&active_rules).sort(self) {
+            Err(SortError::Cycle(cycle)) => return Err(RepairError::Cycle(cycle)),
+            Ok(TopologicalOrder::Unique(order)) => order,
+            Ok(TopologicalOrder::Ambiguous { .. }) if self.len() > MAX_EXACT_REPAIR =>
+                return Err(RepairError::TooLarge { pages: self.len(), limit: MAX_EXACT_REPAIR }),
+            Ok(TopologicalOrder::Ambiguous { .. }) => fewest_inversions(self, &active_rules),
+        };
+
+        Ok(Repair { swaps: adjacent_swaps(self, &target), order: target })
+    }
+}
+
+// DP over the set of already placed positions. Placing position `i` costs one
+// inversion for every position before `i` that is still unplaced.
+fn fewest_inversions(update: &[usize], rules: &[Rule]) -> Vec<usize> {
+    let n = update.len();
+    let position = |page: usize| update.iter().position(|&p| p == page).unwrap();
+    let mut must_follow = vec![0usize; n];
+    for rule in rules {
+        must_follow[position(rule.y)] |= 1 << position(rule.x);
+    }
+
+    let full = (1usize << n) - 1;
+    let mut cost = vec![usize::MAX; 1 << n];
+    let mut choice = vec![0usize; 1 << n];
+    cost[0] = 0;
+    for placed in 0..full {
+        if cost[placed] == usize::MAX {
+            continue;
+        }
+        for i in (0..n).filter(|&i| placed & (1 << i) == 0 && must_follow[i] & !placed == 0) {
+            let earlier_unplaced = (!placed & ((1 << i) - 1)).count_ones() as usize;
+            let next = placed | (1 << i);
+            if cost[placed] + earlier_unplaced < cost[next] {
+                cost[next] = cost[placed] + earlier_unplaced;
+                choice[next] = i;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(n);
+    let mut placed = full;
+    while placed != 0 {
+        order.push(update[choice[placed]]);
+        placed &= !(1 << choice[placed]);
+    }
+    order.reverse();
+    order
+}
+
+// Bubbles each page of `target` into place; this uses exactly as many swaps
+// as there are inversions between the two orders.
+fn adjacent_swaps(update: &[usize], target: &[usize]) -> Vec<Swap> {
+    let mut current = update.to_vec();
+    let mut swaps = Vec::new();
+    for (i, page) in target.iter().enumerate() {
+        let mut j = current.iter().position(|p| p == page).unwrap();
+        while j > i {
+            swaps.push(Swap { index: j - 1, pages: (current[j - 1], current[j]) });
+            current.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+    swaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::parser::parse_rule_line;
+
+    fn example_rules() -> Vec<Rule> {
+        crate::EXAMPLE_RULES.lines().map(|line| parse_rule_line(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_repair_unique() {
+        let update = vec![97, 13, 75, 29, 47];
+
+        let repair = update.repair_minimal(&IndexedRules::new(&example_rules())).unwrap();
+
+        assert_eq!(repair.order, vec![97, 75, 47, 29, 13]);
+        assert_eq!(repair.swaps.len(), 4);
+        assert_eq!(repair.swaps[0], Swap { index: 1, pages: (13, 75) });
+    }
+
+    #[test]
+    fn test_repair_valid_needs_no_swaps() {
+        let update = vec![75, 47, 61, 53, 29];
+
+        assert_eq!(update.repair_minimal(&IndexedRules::new(&example_rules())).unwrap().swaps, vec![]);
+    }
+
+    #[test]
+    fn test_repair_ambiguous_picks_fewest_swaps() {
+        // 3 only has to precede 1; moving it one step beats sorting everything.
+        let update = vec![2, 1, 3, 4];
+        let rules = [Rule::new(3, 1)];
+
+        let repair = update.repair_minimal(&IndexedRules::new(&rules)).unwrap();
+
+        assert_eq!(repair.order, vec![2, 3, 1, 4]);
+        assert_eq!(repair.swaps, vec![Swap { index: 1, pages: (1, 3) }]);
+    }
+
+    #[test]
+    fn test_repair_cycle() {
+        let update = vec![1, 2, 3];
+        let rules = [Rule::new(1, 2), Rule::new(2, 3), Rule::new(3, 1)];
+
+        assert!(matches!(update.repair_minimal(&IndexedRules::new(&rules)), Err(RepairError::Cycle(_))));
+    }
+}
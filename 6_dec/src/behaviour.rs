@@ -0,0 +1,198 @@
+use crate::guard::Direction;
+
+// How the guard reacts to obstacles and how far one move carries it.
+pub trait GuardBehaviour {
+    // Direction to face after the tile ahead turns out to be blocked.
+    fn turn(&mut self, facing: Direction) -> Direction;
+
+    // Whatever the behaviour remembers between turns, if anything. The guard
+    // only loops if it is back on a tile, facing the same way, with the same
+    // state.
+    fn state(&self) -> Option<u64> {
+        None
+    }
+
+    // Whether one move carries the guard all the way to the next obstacle
+    // instead of a single tile.
+    fn slides(&self) -> bool {
+        false
+    }
+
+    // Whether the guard always turns right, which is all the jump tables of
+    // `Simulation` know how to follow.
+    fn fits_jump_tables(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurnRight;
+
+impl GuardBehaviour for TurnRight {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        facing.turn_right()
+    }
+
+    fn fits_jump_tables(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurnLeft;
+
+impl GuardBehaviour for TurnLeft {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        facing.turn_left()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurnAround;
+
+impl GuardBehaviour for TurnAround {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        facing.reverse()
+    }
+}
+
+// Turns an eighth clockwise, so the guard also walks diagonally.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EightWay;
+
+impl GuardBehaviour for EightWay {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        facing.turn_right_eighth()
+    }
+}
+
+// Picks one of the other cardinal directions with a xorshift generator, so a
+// seed always replays the same walk.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomTurns {
+    rng: u64,
+}
+
+impl RandomTurns {
+    pub fn new(seed: u64) -> RandomTurns {
+        RandomTurns { rng: seed.max(1) }
+    }
+}
+
+impl GuardBehaviour for RandomTurns {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let others: Vec<Direction> = Direction::CARDINAL.into_iter().filter(|d| *d != facing).collect();
+        others[(self.rng % others.len() as u64) as usize]
+    }
+
+    fn state(&self) -> Option<u64> {
+        Some(self.rng)
+    }
+}
+
+// Wraps another behaviour so the guard only stops, and is only checked for
+// loops, where it runs into something.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Slide<B>(pub B);
+
+impl<B: GuardBehaviour> GuardBehaviour for Slide<B> {
+    fn turn(&mut self, facing: Direction) -> Direction {
+        self.0.turn(facing)
+    }
+
+    fn state(&self) -> Option<u64> {
+        self.0.state()
+    }
+
+    fn slides(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+    use crate::map::{Map, Object, SimulationOutcome};
+    use crate::walker::Walker;
+    use crate::EXAMPLE;
+
+    fn path(outcome: SimulationOutcome) -> HashMap<(isize, isize), Vec<Direction>> {
+        match outcome {
+            SimulationOutcome::Exited { path } => path,
+            outcome => panic!("Expected the guard to exit, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_turn_right_matches_jump_tables() {
+        let map = Map::parse(EXAMPLE).unwrap();
+
+        assert_eq!(Walker::new(&map, TurnRight).walk(), map.walk());
+        assert_eq!(map.solve_with(Slide(TurnRight)), map.solve());
+        assert_eq!(map.loop_placements_with(&Slide(TurnRight), 1), map.solve_with_loop_placement_threads(1));
+
+        let mut looping = map.clone();
+        looping.place((6, 3), Object::Wall).unwrap();
+        let SimulationOutcome::Looped { loop_length, .. } = looping.solve() else {
+            panic!("Guard should loop");
+        };
+        assert!(matches!(looping.solve_with(Slide(TurnRight)), SimulationOutcome::Looped { loop_length: l, .. } if l == loop_length));
+    }
+
+    #[test]
+    fn test_turn_left() {
+        let map = Map::parse("#..\n...\n^..").unwrap();
+
+        assert_eq!(path(map.solve_with(TurnLeft)), HashMap::from([
+            ((2, 0), vec![Direction::Up]),
+            ((1, 0), vec![Direction::Up, Direction::Left]),
+        ]));
+    }
+
+    #[test]
+    fn test_turn_around_loops() {
+        let map = Map::parse(".#.\n...\n.^.\n.#.").unwrap();
+
+        let SimulationOutcome::Looped { loop_start, loop_length, .. } = map.solve_with(TurnAround) else {
+            panic!("Guard should bounce forever");
+        };
+        assert_eq!((loop_start, loop_length), (0, 4));
+    }
+
+    #[test]
+    fn test_eight_way() {
+        let map = Map::parse("#..\n...\n^..").unwrap();
+
+        let path = path(map.solve_with(EightWay));
+        assert_eq!(path[&(1, 0)], vec![Direction::Up, Direction::UpRight]);
+        assert_eq!(path[&(0, 1)], vec![Direction::UpRight]);
+    }
+
+    #[test]
+    fn test_random_turns_replay() {
+        let map = Map::parse(EXAMPLE).unwrap();
+
+        assert_eq!(map.solve_with(RandomTurns::new(7)), map.solve_with(RandomTurns::new(7)));
+
+        // Boxed in, the guard keeps turning without ever repeating its
+        // generator state, so there is no telling it is stuck for good.
+        let boxed = Map::parse("###\n#^#\n###").unwrap();
+        assert_eq!(boxed.solve_with(RandomTurns::new(7)), SimulationOutcome::Undecided { steps: 72 });
+        assert!(boxed.loop_placements_with(&RandomTurns::new(7), 1).is_empty());
+
+        // Coming back to a tile with a different generator state is no loop.
+        let open = Map::parse("###.#\n#...#\n#^..#\n#####").unwrap();
+        for seed in 1..200 {
+            assert!(matches!(open.solve_with(RandomTurns::new(seed)), SimulationOutcome::Exited { .. }), "seed {}", seed);
+        }
+
+        let mut random = RandomTurns::new(42);
+        let turns: HashSet<Direction> = (0..100).map(|_| random.turn(Direction::Up)).collect();
+        assert_eq!(turns, HashSet::from([Direction::Right, Direction::Down, Direction::Left]));
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{self, BufRead, Write};
+
+use std::collections::HashSet;
+
+use crate::map::{Map, Object};
+use crate::report::render_path;
+use crate::walker::WalkEnd;
+
+const HELP: &str = "Commands: w <row> <col> (wall), x <row> <col> (clear), s <file> (save), p (print), q (quit)";
+
+// Line-based editor: every edit re-runs the guard and prints the map with its
+// path or loop, so test maps can be built up by hand.
+pub fn run(mut map: Map, threads: usize, input: impl BufRead, mut output: impl Write) -> io::Result<Map> {
+    writeln!(output, "{}", HELP)?;
+    show(&map, threads, &mut output)?;
+
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let coordinates: Vec<isize> = words.iter().skip(1).filter_map(|word| word.parse().ok()).collect();
+
+        let edit = match (words.first().copied(), coordinates.as_slice()) {
+            (Some("w"), [row, col]) => map.place((*row, *col), Object::Wall),
+            (Some("x"), [row, col]) => map.place((*row, *col), Object::Empty),
+            (Some("s"), _) if words.len() == 2 => {
+                match std::fs::write(words[1], format!("{}\n", map)) {
+                    Ok(()) => writeln!(output, "Saved to {}", words[1])?,
+                    Err(e) => writeln!(output, "Could not save: {}", e)?,
+                }
+                continue;
+            },
+            (Some("p"), _) => Ok(()),
+            (Some("q"), _) => break,
+            (None, _) => continue,
+            _ => {
+                writeln!(output, "{}", HELP)?;
+                continue;
+            },
+        };
+
+        match edit {
+            Ok(()) => show(&map, threads, &mut output)?,
+            Err(e) => writeln!(output, "{}", e)?,
+        }
+    }
+    Ok(map)
+}
+
+fn show(map: &Map, threads: usize, output: &mut impl Write) -> io::Result<()> {
+    let (states, end) = map.walk();
+    match end {
+        WalkEnd::Looped(loop_start) => {
+            write!(output, "{}", render_path(map, &states[loop_start..], None))?;
+            writeln!(output, "Guard loops: enters a cycle of {} steps after {} steps", states.len() - loop_start, loop_start)?;
+        },
+        WalkEnd::Undecided => {
+            write!(output, "{}", render_path(map, &states, None))?;
+            writeln!(output, "Guard still walking after {} steps", states.len() - 1)?;
+        },
+        WalkEnd::Exited => {
+            write!(output, "{}", render_path(map, &states, None))?;
+            let tiles: HashSet<_> = states.iter().map(|(position, _)| position).collect();
+            writeln!(output, "Guard leaves after {} steps over {} tiles", states.len(), tiles.len())?;
+            writeln!(output, "{} loop placements", map.solve_with_loop_placement_threads(threads).len())?;
+        },
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_session() {
+        let map = Map::parse(".#...\n....#\n.^...\n...#.").unwrap();
+        let commands = "w 2 0\nx 0 1\nw 2 1\nbogus\nw 9 9\nq\nw 1 1\n";
+
+        let mut output = Vec::new();
+        let edited = run(map, 2, commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(edited.to_string(), ".....\n....#\n#^...\n...#.");
+        assert!(output.contains("Guard loops"));
+        assert!(output.contains("Guard leaves after 3 steps over 3 tiles"));
+        assert!(output.contains("The guard stands at (2, 1)"));
+        assert!(output.contains("Position out of bounds: (9, 9)"));
+        assert!(output.contains(HELP));
+    }
+}
@@ -1,12 +1,81 @@
 type Position = (isize, isize);
 
 
-#[derive(Clone, PartialEq, Hash, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Hash, Eq, Debug)]
 pub enum Direction {
     Up,
     Right,
     Down,
     Left,
+    UpRight,
+    DownRight,
+    DownLeft,
+    UpLeft,
+}
+
+impl Direction {
+    pub const CARDINAL: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+    pub const ALL: [Direction; 8] = [
+        Direction::Up, Direction::UpRight, Direction::Right, Direction::DownRight,
+        Direction::Down, Direction::DownLeft, Direction::Left, Direction::UpLeft,
+    ];
+
+    pub fn delta(&self) -> Position {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Right => (0, 1),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::DownRight => (1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::UpLeft => (-1, -1),
+        }
+    }
+
+    pub fn turn_right(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+            Direction::UpRight => Direction::DownRight,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpLeft,
+            Direction::UpLeft => Direction::UpRight,
+        }
+    }
+
+    pub fn reverse(&self) -> Direction {
+        self.turn_right().turn_right()
+    }
+
+    pub fn turn_left(&self) -> Direction {
+        self.reverse().turn_right()
+    }
+
+    // A quarter of `turn_right`, going through the diagonals.
+    pub fn turn_right_eighth(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::UpRight,
+            Direction::UpRight => Direction::Right,
+            Direction::Right => Direction::DownRight,
+            Direction::DownRight => Direction::Down,
+            Direction::Down => Direction::DownLeft,
+            Direction::DownLeft => Direction::Left,
+            Direction::Left => Direction::UpLeft,
+            Direction::UpLeft => Direction::Up,
+        }
+    }
+
+    // Position of a cardinal direction in the simulation's jump tables.
+    pub fn index(&self) -> Option<usize> {
+        Direction::CARDINAL.iter().position(|direction| direction == self)
+    }
+
+    pub fn from_index(index: usize) -> Direction {
+        Direction::CARDINAL[index % 4]
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -26,21 +95,8 @@ impl Guard {
     }
 
     pub fn move_ahead(&self, position: &Position) -> Position {
-        match self.direction {
-            Direction::Up => (position.0 - 1, position.1),
-            Direction::Right => (position.0, position.1 + 1),
-            Direction::Down => (position.0 + 1, position.1),
-            Direction::Left => (position.0, position.1 - 1),
-        }
-    }
-
-    pub fn turn_right(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-        }
+        let delta = self.direction.delta();
+        (position.0 + delta.0, position.1 + delta.1)
     }
 }
 
@@ -70,15 +126,26 @@ mod tests {
 
     #[test]
     fn test_turn_right() {
-        let mut guard = Guard::new(Direction::Up);
-        assert_eq!(guard.direction(), &Direction::Up);
-        guard.turn_right();
-        assert_eq!(guard.direction(), &Direction::Right);
-        guard.turn_right();
-        assert_eq!(guard.direction(), &Direction::Down);
-        guard.turn_right();
-        assert_eq!(guard.direction(), &Direction::Left);
-        guard.turn_right();
-        assert_eq!(guard.direction(), &Direction::Up);
+        let mut direction = Direction::Up;
+        direction = direction.turn_right();
+        assert_eq!(direction, Direction::Right);
+        direction = direction.turn_right();
+        assert_eq!(direction, Direction::Down);
+        direction = direction.turn_right();
+        assert_eq!(direction, Direction::Left);
+        direction = direction.turn_right();
+        assert_eq!(direction, Direction::Up);
+    }
+
+    #[test]
+    fn test_direction_turns() {
+        for direction in Direction::CARDINAL {
+            assert_eq!(direction.turn_left().turn_right(), direction);
+            assert_eq!(direction.turn_right_eighth().turn_right_eighth(), direction.turn_right());
+            assert_eq!(direction.reverse().delta(), (-direction.delta().0, -direction.delta().1));
+        }
+        assert_eq!(Direction::UpLeft.turn_right_eighth(), Direction::Up);
+        assert_eq!(Direction::Left.index().map(Direction::from_index), Some(Direction::Left));
+        assert_eq!(Direction::UpLeft.index(), None);
     }
 }
\ No newline at end of file
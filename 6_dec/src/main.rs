@@ -1,9 +1,18 @@
+mod behaviour;
+mod editor;
 mod guard;
 mod map;
+mod patrol;
+mod replay;
+mod report;
+mod simulation;
+mod tiles;
+mod walker;
 
-use std::collections::HashSet;
-
-use map::Map;
+use behaviour::{EightWay, GuardBehaviour, RandomTurns, Slide, TurnAround, TurnLeft, TurnRight};
+use map::{Map, SimulationOutcome};
+use patrol::{Collision, Patrol, PatrolOutcome};
+use replay::GuardSimulation;
 
 
 use load_input::read_file_contents;
@@ -11,12 +20,183 @@ use load_input::read_file_contents;
 fn main() {
     let input = read_file_contents("input.txt").unwrap();
 
-    let orig_map = Map::parse(&input);
-    let mut map: Map = orig_map.clone();
-    let super_solver = orig_map.clone();
-    
-    let no_placements = super_solver.solve_with_loop_placement().len();
-    let no_distinct_places     = map.solve().unwrap().into_iter().collect::<HashSet<_>>().len();
-    println!("Distinct tiles {}", no_distinct_places);
+    let map = match Map::parse(&input) {
+        Ok(map) => map,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("trace") {
+        trace(&map, &args[2..]);
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("edit") {
+        edit(map, args.get(2).filter(|arg| !arg.starts_with("--")), threads(&args));
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("report") {
+        report_placements(&map, threads(&args), args.iter().any(|arg| arg == "--render"));
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("patrol") {
+        patrol(&map, &args[2..]);
+        return;
+    }
+
+    let threads = threads(&args);
+    if let Some(name) = args.iter().position(|arg| arg == "--behaviour").and_then(|i| args.get(i + 1)) {
+        match name.split_once(':').unwrap_or((name, "")) {
+            ("right", _) => report(&map, TurnRight, threads),
+            ("left", _) => report(&map, TurnLeft, threads),
+            ("around", _) => report(&map, TurnAround, threads),
+            ("eight", _) => report(&map, EightWay, threads),
+            ("slide", _) => report(&map, Slide(TurnRight), threads),
+            ("random", seed) => report(&map, RandomTurns::new(seed.parse().unwrap_or(1)), threads),
+            _ => eprintln!("Unknown behaviour '{}', expected right, left, around, eight, slide or random:<seed>", name),
+        }
+        return;
+    }
+
+    let no_placements = map.solve_with_loop_placement_threads(threads).len();
+    print_outcome(map.solve());
     println!("Placements {}", no_placements);
 }
+
+fn threads(args: &[String]) -> usize {
+    args.iter().position(|arg| arg == "--threads")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1)
+}
+
+fn report<B: GuardBehaviour + Clone>(map: &Map, behaviour: B, threads: usize) {
+    print_outcome(map.solve_with(behaviour.clone()));
+    println!("Placements {}", map.loop_placements_with(&behaviour, threads).len());
+}
+
+fn print_outcome(outcome: SimulationOutcome) {
+    match outcome {
+        SimulationOutcome::Exited { path } => println!("Distinct tiles {}", path.len()),
+        SimulationOutcome::Looped { loop_start, loop_length, .. } => {
+            println!("Guard never leaves: loops after {} steps with period {}", loop_start, loop_length)
+        },
+        SimulationOutcome::Undecided { steps } => println!("Guard still walking after {} steps", steps),
+    }
+}
+
+// Prints every event of the guard's walk, with an optional extra obstacle:
+// `trace [<row> <col>] [--step]`. With `--step` the walk is stepped through
+// interactively.
+fn trace(map: &Map, args: &[String]) {
+    let coordinates: Vec<isize> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+    let simulation = match coordinates.as_slice() {
+        [] => GuardSimulation::new(map),
+        [row, col] => match GuardSimulation::with_obstacle(map, (*row, *col)) {
+            Ok(simulation) => simulation,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            },
+        },
+        _ => {
+            eprintln!("Usage: trace [<row> <col>] [--step]");
+            return;
+        },
+    };
+
+    if args.iter().any(|arg| arg == "--step") {
+        if let Err(e) = replay::step_through(simulation, std::io::stdin().lock(), std::io::stdout()) {
+            eprintln!("{}", e);
+        }
+        return;
+    }
+    for (step, event) in simulation {
+        println!("{:>6} {}", step, event);
+    }
+}
+
+// Runs every guard on the map at once: `patrol [block|swap|merge] [file]`.
+fn patrol(map: &Map, args: &[String]) {
+    let rule = match args.first().map_or(Ok(Collision::Block), |rule| rule.parse()) {
+        Ok(rule) => rule,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
+    let map = match args.get(1) {
+        Some(file) => match load_map(file) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            },
+        },
+        None => map.clone(),
+    };
+
+    println!("{} guards", map.guards().len());
+    match Patrol::new(rule, TurnRight).run(&map) {
+        PatrolOutcome::Exited { ticks, visited } => println!("All guards left after {} ticks, {} distinct tiles", ticks, visited.len()),
+        PatrolOutcome::Looped { loop_start, loop_length } => {
+            println!("Guards loop after {} ticks with period {}", loop_start, loop_length)
+        },
+        PatrolOutcome::Undecided { ticks } => println!("Guards still walking after {} ticks", ticks),
+    }
+}
+
+// Lists every loop placement in the order the guard reaches it:
+// `report [--render] [--threads N]`.
+fn report_placements(map: &Map, threads: usize, render: bool) {
+    for report in map.loop_placement_report(threads) {
+        println!("{:?}: reached at step {}, loop of {} steps over {} tiles",
+            report.obstruction, report.first_reached, report.loop_length, report.loop_tiles.len());
+        if render {
+            println!("{}", report.render(map));
+        }
+    }
+}
+
+// `edit [<file>] [--threads N]`: opens `input.txt`, or the given map file,
+// in the line editor.
+fn edit(map: Map, file: Option<&String>, threads: usize) {
+    let map = match file {
+        Some(file) => match load_map(file) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            },
+        },
+        None => map,
+    };
+
+    if let Err(e) = editor::run(map, threads, std::io::stdin().lock(), std::io::stdout()) {
+        eprintln!("{}", e);
+    }
+}
+
+fn load_map(file: &str) -> Result<Map, String> {
+    let input = read_file_contents(file).map_err(|e| e.to_string())?;
+    Map::parse(&input).map_err(|e| e.to_string())
+}
+
+// The map from the puzzle description, shared by the tests.
+#[cfg(test)]
+pub static EXAMPLE: &str =
+"....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...";
@@ -1,86 +1,143 @@
-use std::{collections::{HashMap, HashSet}, isize};
+use std::collections::{HashMap, HashSet};
 
 use crate::guard::{
-    self, Direction, Guard
+    Direction, Guard
 };
+use crate::behaviour::{GuardBehaviour, TurnRight};
+use crate::simulation::Simulation;
+use crate::tiles::{Tile, TileRegistry};
+use crate::walker::{WalkEnd, Walker};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Object {
     Guard(Guard),
     Wall,
     Empty,
+    // Any other kind of tile, see `TileKind`.
+    Tile(Tile),
 }
 
-type Position = (isize, isize);
+pub type Position = (isize, isize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    InvalidCharacter { row: usize, col: usize, found: char },
+    RaggedRow(usize),
+    MissingGuard,
+    OutOfBounds(Position),
+    OccupiedByGuard(Position),
+    UnpairedPortal(char),
+    PortalTile(Position),
+    NotPlaceable(Position),
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MapError::InvalidCharacter { row, col, found } => write!(f, "Invalid character '{}' at ({}, {})", found, row, col),
+            MapError::RaggedRow(row) => write!(f, "Row {} differs in length from the first row", row),
+            MapError::MissingGuard => write!(f, "No guard on the map"),
+            MapError::OutOfBounds(position) => write!(f, "Position out of bounds: ({}, {})", position.0, position.1),
+            MapError::OccupiedByGuard(position) => write!(f, "The guard stands at ({}, {})", position.0, position.1),
+            MapError::UnpairedPortal(letter) => write!(f, "Portal '{}' must appear exactly twice", letter),
+            MapError::PortalTile(position) => write!(f, "There is a portal at ({}, {})", position.0, position.1),
+            MapError::NotPlaceable(position) => write!(f, "Only walls and empty tiles can be placed at ({}, {})", position.0, position.1),
+        }
+    }
+}
+
+// Every state is a (position, direction) pair in the order the guard was in it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationOutcome {
+    Exited { path: HashMap<Position, Vec<Direction>> },
+    // `path_to_loop` runs up to the first repeated state, so the cycle itself
+    // is `path_to_loop[loop_start..]`.
+    Looped { loop_start: usize, loop_length: usize, path_to_loop: Vec<(Position, Direction)> },
+    // A behaviour with state of its own, like `RandomTurns`, kept the guard
+    // walking without telling whether it ever leaves.
+    Undecided { steps: usize },
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Map {
     objects: Vec<Vec<Object>>,
     guard_pos: Position,
+    // Each portal tile mapped to its partner. Portals can't be edited, so
+    // these stay paired.
+    portals: HashMap<Position, Position>,
 }
 
 impl Map {
-    pub fn parse_and_place(input: &str, position: &Position) -> Map {
-        let mut map = Map::parse(input);
-        map.set(*position, Object::Wall);
-        map
+    pub fn parse(input: &str) -> Result<Map, MapError> {
+        Map::parse_with(input, &TileRegistry::default())
     }
 
-    pub fn parse(input: &str) -> Map {
-            let mut guard_pos: Position = (0, 0);
-        let objects = input.lines().enumerate().map(|(line_i, line)| {
-            line.chars().enumerate().map(|(char_i, char)| {
+    pub fn parse_with(input: &str, registry: &TileRegistry) -> Result<Map, MapError> {
+        let mut guard_pos = None;
+        let mut objects: Vec<Vec<Object>> = Vec::new();
+        for (line_i, line) in input.lines().enumerate() {
+            let row = line.chars().enumerate().map(|(char_i, char)| {
                 const GUARDS: [char;4] = ['v', '^', '<', '>'];
-                const WALLS: char = '#';
-                const EMPTY: char = '.';
-    
+
                 match char {
-                    WALLS => Object::Wall,
-                    EMPTY => Object::Empty,
                     g if GUARDS.contains(&g) => {
                         let direction = match &g {
                             'v' => Direction::Down,
                             '^' => Direction::Up,
                             '<' => Direction::Left,
-                            '>' => Direction::Right,
-                            _ => panic!("Invalid guard direction"),
+                            _ => Direction::Right,
                         };
-                        let guard = Guard::new(direction);
-                        guard_pos = (line_i as isize, char_i as isize);
-                        Object::Guard(guard)
+                        guard_pos.get_or_insert((line_i as isize, char_i as isize));
+                        Ok(Object::Guard(Guard::new(direction)))
                     },
-                    _ => panic!("Invalid character in map"),
+                    found => registry.get(found)
+                        .cloned()
+                        .ok_or(MapError::InvalidCharacter { row: line_i, col: char_i, found }),
                 }
-            }).collect()
-        }).collect();
+            }).collect::<Result<Vec<_>, _>>()?;
 
-        Map {
-            objects,
-            guard_pos
+            if objects.first().is_some_and(|first| first.len() != row.len()) {
+                return Err(MapError::RaggedRow(line_i));
+            }
+            objects.push(row);
         }
-    }
 
-    fn set (&mut self, position: Position, object: Object) -> Result<(), String>  {
-        let x = if position.0 >= 0 { 
-            position.0 as usize
-        } else {
-            return Err(format!("Invalid 'x' position: '{}'", position.0));
-        };
-        let y = if position.1 >= 0 { 
-            position.1 as usize
-        } else {
-            return Err(format!("Invalid 'y' position: '{}'", position.1));
+        let mut map = Map {
+            objects,
+            guard_pos: guard_pos.ok_or(MapError::MissingGuard)?,
+            portals: HashMap::new(),
         };
+        map.portals = map.pair_portals()?;
+        Ok(map)
+    }
 
-        if x < self.objects.len() && y < self.objects[x].len() {
-            self.objects[x][y] = object;
-            Ok(())
-        } else {
-            Err(format!("Position out of bounds: ({}, {})", x, y))
+    fn pair_portals(&self) -> Result<HashMap<Position, Position>, MapError> {
+        let mut by_letter: HashMap<char, Vec<Position>> = HashMap::new();
+        for (row, col, object) in self.tiles() {
+            if let Some(letter) = object.link() {
+                by_letter.entry(letter).or_default().push((row as isize, col as isize));
+            }
+        }
+
+        let mut letters: Vec<_> = by_letter.into_iter().collect();
+        letters.sort();
+        let mut portals = HashMap::new();
+        for (letter, positions) in letters {
+            let [a, b] = positions[..] else {
+                return Err(MapError::UnpairedPortal(letter));
+            };
+            portals.insert(a, b);
+            portals.insert(b, a);
         }
+        Ok(portals)
+    }
+
+    // Where stepping onto the portal at `position` takes the guard.
+    pub fn portal_exit(&self, position: Position) -> Option<Position> {
+        self.portals.get(&position).copied()
     }
 
-    fn get(&self, position: &Position) -> Option<&Object> {
+    pub fn get(&self, position: &Position) -> Option<&Object> {
         let x = if position.0 >= 0 { 
             position.0 as usize
         } else {
@@ -105,115 +162,168 @@ impl Map {
         }
     }
 
-    fn get_guard_pos(&self) -> Position {
-        self.guard_pos
+    // The first guard in reading order; the single-guard solvers ignore the rest.
+    pub fn guard(&self) -> (Position, &Guard) {
+        (self.guard_pos, self.get_guard())
     }
 
+    pub fn guards(&self) -> Vec<(Position, &Guard)> {
+        self.tiles()
+            .filter_map(|(row, col, object)| match object {
+                Object::Guard(guard) => Some(((row as isize, col as isize), guard)),
+                _ => None,
+            })
+            .collect()
+    }
 
-    fn check_valid_position(&self, position: &Position) -> bool {
-        let line_range = (0 as isize, self.objects.len() as isize);
-        let char_range = (0 as isize, self.objects[0].len() as isize);
+    // Only walls and empty tiles can go on a tile without a guard or portal.
+    // Other tiles would need the checks `parse` does.
+    pub fn check_place(&self, position: Position, object: &Object) -> Result<(), MapError> {
+        if !matches!(object, Object::Wall | Object::Empty) {
+            return Err(MapError::NotPlaceable(position));
+        }
+        match self.get(&position) {
+            Some(Object::Guard(_)) => Err(MapError::OccupiedByGuard(position)),
+            Some(object) if object.link().is_some() => Err(MapError::PortalTile(position)),
+            Some(_) => Ok(()),
+            None => Err(MapError::OutOfBounds(position)),
+        }
+    }
 
-        let x = position.0;
-        let y = position.1;
+    pub fn place(&mut self, position: Position, object: Object) -> Result<(), MapError> {
+        self.check_place(position, &object)?;
+        self.objects[position.0 as usize][position.1 as usize] = object;
+        Ok(())
+    }
 
-        x >= line_range.0 && char_range.0 >= 0 && x < line_range.1 && y < char_range.1
+    pub fn size(&self) -> (usize, usize) {
+        (self.objects.len(), self.objects.first().map_or(0, Vec::len))
     }
 
-    fn move_guard(&mut self, new_pos: Position) -> Result<(), String> {
-        let guard_tile = Object::Guard(self.get_guard().clone());
-        let guard_post = self.guard_pos;
+    pub fn tiles(&self) -> impl Iterator<Item = (usize, usize, &Object)> {
+        self.objects.iter().enumerate().flat_map(|(row, line)| {
+            line.iter().enumerate().map(move |(col, object)| (row, col, object))
+        })
+    }
 
-        self.set(new_pos, guard_tile)?;
-        self.set(guard_post, Object::Empty)?;
-        self.guard_pos = new_pos;
-        Ok(())
+    pub fn solve(&self) -> SimulationOutcome {
+        let (states, end) = self.walk();
+        Map::outcome(states, end)
     }
 
-    fn is_valid_guard_position(&self) -> bool {
-        self.check_valid_position(&self.guard_pos)
+    pub fn solve_with<B: GuardBehaviour>(&self, behaviour: B) -> SimulationOutcome {
+        let (states, end) = self.walk_with(behaviour);
+        Map::outcome(states, end)
     }
-    
-    // Returns Err True if guard is stuck in a loop
-    pub fn solve(&mut self) -> Result<HashMap<Position, Vec<Direction>>, bool> {
-        let mut path: HashMap<Position, Vec<Direction>> = HashMap::new();
-        
-        let mut guard = self.get_guard().clone();
-        path.entry(self.guard_pos).or_insert(vec![]).push(guard.direction().clone());
-
-        while self.is_valid_guard_position() {
-
-            let guard_pos: Position = self.guard_pos;
-            
-            let new_pos = guard.move_ahead(&guard_pos);
-            match self.get(&new_pos) {
-                Some(Object::Wall) => {
-                    guard.turn_right();
-                },
-                Some(Object::Empty) => {
-                    if !self.check_valid_position(&new_pos) {
-                        panic!("Guard not in ");
-                    }
-
-                    self.move_guard(new_pos).unwrap();
-                },
-                Some(Object::Guard(_)) => panic!("Guard already at new position!"), 
-                None => break,
-            };
 
-            if path.entry(self.guard_pos).or_default().contains(guard.direction()) {
-                return Err(true);
-            }
+    pub fn walk(&self) -> (Vec<(Position, Direction)>, WalkEnd) {
+        self.walk_with(TurnRight)
+    }
+
+    // Every state of the guard in order, and how the walk ended. A guard
+    // that always turns right on a plain map goes through the precomputed
+    // jump tables; anything else takes single steps.
+    fn walk_with<B: GuardBehaviour>(&self, behaviour: B) -> (Vec<(Position, Direction)>, WalkEnd) {
+        let Some(simulation) = self.simulation(&behaviour) else {
+            return Walker::new(self, behaviour).walk();
+        };
+        let (states, loop_start) = simulation.walk(None);
+        let states = states.into_iter()
+            .map(|(cell, direction)| (simulation.position(cell), Direction::from_index(direction)))
+            .collect();
+        (states, loop_start.map_or(WalkEnd::Exited, WalkEnd::Looped))
+    }
+
+    fn simulation<B: GuardBehaviour>(&self, behaviour: &B) -> Option<Simulation> {
+        behaviour.fits_jump_tables().then(|| Simulation::new(self)).flatten()
+    }
 
-            path.entry(self.guard_pos).or_insert(vec![]).push(guard.direction().clone());
+    fn outcome(states: Vec<(Position, Direction)>, end: WalkEnd) -> SimulationOutcome {
+        match end {
+            WalkEnd::Looped(loop_start) => {
+                SimulationOutcome::Looped { loop_start, loop_length: states.len() - loop_start, path_to_loop: states }
+            },
+            WalkEnd::Undecided => SimulationOutcome::Undecided { steps: states.len() - 1 },
+            WalkEnd::Exited => {
+                let mut path: HashMap<Position, Vec<Direction>> = HashMap::new();
+                for (position, direction) in states {
+                    path.entry(position).or_default().push(direction);
+                }
+                SimulationOutcome::Exited { path }
+            },
         }
+    }
 
-        Ok(path)
+    pub fn solve_with_loop_placement_threads(&self, threads: usize) -> HashSet<Position> {
+        self.loop_placements_with(&TurnRight, threads)
     }
 
-    pub fn solve_with_loop_placement(&self) -> HashSet<Position> {
-        let original_map = self.clone();
-        let mut map = original_map.clone();
+    // Tiles on the guard's path where an extra obstacle traps it in a loop.
+    pub fn loop_placements_with<B: GuardBehaviour + Clone>(&self, behaviour: &B, threads: usize) -> HashSet<Position> {
+        if let Some(simulation) = self.simulation(behaviour) {
+            return simulation.loop_placements(threads)
+                .into_iter()
+                .map(|cell| simulation.position(cell))
+                .collect();
+        }
+        self.placement_cycles_with(behaviour, threads).into_iter().map(|(position, _)| position).collect()
+    }
 
-        let illegal_pos = map.get_guard_pos();
-        let guard_path = map.solve().unwrap();
+    // The same placements, each with every state of the loop it causes.
+    // Without jump tables every candidate is walked again from the start, on
+    // a single thread.
+    pub fn placement_cycles_with<B: GuardBehaviour + Clone>(&self, behaviour: &B, threads: usize) -> Vec<(Position, Vec<(Position, Direction)>)> {
+        if let Some(simulation) = self.simulation(behaviour) {
+            return simulation.loop_cycles(threads)
+                .into_iter()
+                .map(|(cell, cycle)| {
+                    let cycle = cycle.into_iter()
+                        .map(|(cell, direction)| (simulation.position(cell), Direction::from_index(direction)))
+                        .collect();
+                    (simulation.position(cell), cycle)
+                })
+                .collect();
+        }
 
-        guard_path.iter().flat_map(|(pos, dirs)|{
-            let possible_placements: HashSet<_> = dirs.iter().map(|dir| {
-                let new_pos = Guard::new(dir.clone()).move_ahead(&pos);
-                if new_pos == illegal_pos {
+        let (states, _) = Walker::new(self, behaviour.clone()).walk();
+        let path: HashSet<Position> = states.into_iter().map(|(position, _)| position).collect();
+        path.into_iter()
+            .filter(|&position| position != self.guard_pos && self.get(&position) == Some(&Object::Empty))
+            .filter_map(|position| {
+                let walker = Walker::new(self, behaviour.clone()).with_obstacle(position).ok()?;
+                let (mut states, WalkEnd::Looped(loop_start)) = walker.walk() else {
                     return None;
-                }
-                Some(new_pos)
-            }).filter_map(|x| x).collect();
+                };
+                Some((position, states.split_off(loop_start)))
+            })
+            .collect()
+    }
+}
 
-            possible_placements.iter().map(|new_pos| {
-                let mut map = original_map.clone();
-                let new_pos = new_pos.clone();
 
-                match map.set(new_pos, Object::Wall){
-                    Ok(_) => {},
-                    Err(_) => return None,
-                }
-                
-                match map.solve() {
-                    Ok(_) => None,
-                    Err(x) if x => Some(new_pos),
-                    _ => panic!("Unexpected error"),
-                }
-            }).filter_map(|x| x).collect::<Vec<_>>()
-        }).collect::<HashSet<_>>()
+// Tiles are written with their default symbols, so a map read with a custom
+// registry won't parse back the same. Diagonal guards and arrows have no
+// symbol at all and come out as '*'.
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (row_i, row) in self.objects.iter().enumerate() {
+            if row_i > 0 {
+                writeln!(f)?;
+            }
+            for object in row {
+                write!(f, "{}", object.symbol())?;
+            }
+        }
+        Ok(())
     }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
-
-    use crate::map;
-
     use super::*;
+    use crate::tiles::{Ice, Portal};
+    use crate::EXAMPLE;
 
     #[test]
     fn test_parse() {
@@ -221,7 +331,7 @@ mod tests {
 "#..
 #.#
 #^#";
-        let map = Map::parse(input);
+        let map = Map::parse(input).unwrap();
         assert_eq!(map.objects.len(), 3);
         assert_eq!(map.objects[0].len(), 3);
         assert_eq!(map.guard_pos, (2, 1));
@@ -243,7 +353,7 @@ mod tests {
 "#..
 #.#
 #^#";
-        let map = Map::parse(input);
+        let map = Map::parse(input).unwrap();
         let guard = map.get_guard();
 
         assert_eq!(guard.direction(), &Direction::Up);
@@ -257,8 +367,10 @@ mod tests {
 "####
 #..#
 #^.#";
-        let mut map = Map::parse(input);
-        let guard_path = map.solve().unwrap();
+        let map = Map::parse(input).unwrap();
+        let SimulationOutcome::Exited { path: guard_path } = map.solve() else {
+            panic!("Guard should exit");
+        };
 
         let mut expected_path = HashMap::new();
         expected_path.insert((2, 1), vec![Direction::Up]);
@@ -272,21 +384,75 @@ mod tests {
     
     #[test]
     fn test_loop_placement() {
-        let input = 
-"....#.....
-.........#
-..........
-..#.......
-.......#..
-..........
-.#..^.....
-........#.
-#.........
-......#...";
+        let mut map = Map::parse(EXAMPLE).unwrap();
+        map.place((6, 3), Object::Wall).unwrap();
+
+        match map.solve() {
+            SimulationOutcome::Looped { loop_start, loop_length, path_to_loop } => {
+                assert_eq!(loop_start, 0);
+                assert_eq!(path_to_loop.len(), loop_length);
+                assert_eq!(path_to_loop[0], ((6, 4), Direction::Up));
+                assert_eq!(path_to_loop[1], ((5, 4), Direction::Up));
+            },
+            outcome => panic!("Expected a loop, got {:?}", outcome),
+        }
+    }
 
-        let mut map = Map::parse_and_place(input, &(6,3));
+    #[test]
+    fn test_loop_after_lead_in() {
+        let input =
+"..#...
+.....#
+.#....
+....#.
+......
+..^...";
+
+        let SimulationOutcome::Looped { loop_start, loop_length, path_to_loop } = map_solve(input) else {
+            panic!("Guard should loop");
+        };
+        assert_eq!(path_to_loop[..loop_start], [((5, 2), Direction::Up), ((4, 2), Direction::Up), ((3, 2), Direction::Up)]);
+        assert_eq!(path_to_loop[loop_start], ((2, 2), Direction::Up));
+        assert_eq!(loop_length, 10);
+    }
 
-        assert!(map.solve().is_err());
+    fn map_solve(input: &str) -> SimulationOutcome {
+        Map::parse(input).unwrap().solve()
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let input = "#.~%\n.A^→\n.A..";
+        let map = Map::parse(input).unwrap();
+
+        assert_eq!(map.to_string(), input);
+        assert_eq!(Map::parse(&map.to_string()), Ok(map.clone()));
+
+        let mut edited = map.clone();
+        edited.place((2, 3), Object::Wall).unwrap();
+        assert_eq!(edited.to_string(), "#.~%\n.A^→\n.A.#");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Map::parse("..\n.x\n^."), Err(MapError::InvalidCharacter { row: 1, col: 1, found: 'x' }));
+        assert_eq!(Map::parse("..\n.\n^."), Err(MapError::RaggedRow(1)));
+        assert_eq!(Map::parse("..\n.."), Err(MapError::MissingGuard));
+
+        let mut map = Map::parse(".#\n^.").unwrap();
+        assert_eq!(map.place((2, 0), Object::Wall), Err(MapError::OutOfBounds((2, 0))));
+        assert_eq!(map.place((-1, 0), Object::Wall), Err(MapError::OutOfBounds((-1, 0))));
+        assert_eq!(map.place((1, 0), Object::Wall), Err(MapError::OccupiedByGuard((1, 0))));
+
+        let mut map = Map::parse(".<\n^.").unwrap();
+        assert_eq!(map.place((0, 1), Object::Empty), Err(MapError::OccupiedByGuard((0, 1))));
+
+        let mut map = Map::parse(".A\n^A").unwrap();
+        assert_eq!(map.place((0, 1), Object::Wall), Err(MapError::PortalTile((0, 1))));
+        assert_eq!(map.place((0, 0), Object::Tile(Tile::new(Portal('A')))), Err(MapError::NotPlaceable((0, 0))));
+        assert_eq!(map.place((0, 0), Object::Tile(Tile::new(Ice))), Err(MapError::NotPlaceable((0, 0))));
+        assert_eq!(map.place((0, 0), Object::Guard(Guard::new(Direction::Up))), Err(MapError::NotPlaceable((0, 0))));
+        assert_eq!(map.portal_exit((0, 1)), Some((1, 1)));
     }
 
     
@@ -300,21 +466,9 @@ mod tests {
     // then he is then facing the same direction as first time - it is a loop
     #[test]
     fn test_find_placements() {
-        let input = 
-"....#.....
-.........#
-..........
-..#.......
-.......#..
-..........
-.#..^.....
-........#.
-#.........
-......#...";
-
-        let map = Map::parse(input);
-
-        let actual = map.solve_with_loop_placement();
+        let map = Map::parse(EXAMPLE).unwrap();
+
+        let actual = map.solve_with_loop_placement_threads(1);
         // assert_eq!(loop_places, 6);
         let expected: HashSet<(isize, isize)> = HashSet::from_iter(vec![
             (6,3),
@@ -327,6 +481,7 @@ mod tests {
 
         
         assert_eq!(expected, actual);
+        assert_eq!(expected, map.solve_with_loop_placement_threads(4));
     }
 
     
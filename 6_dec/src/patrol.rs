@@ -0,0 +1,306 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::behaviour::GuardBehaviour;
+use crate::guard::Direction;
+use crate::map::{Map, Position};
+use crate::walker::{Step, Walker};
+
+// What happens when a guard walks into a tile another guard holds or claims.
+// Guards earlier in reading order get right of way when two want the same
+// free tile; a guard that is not moving always keeps its tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    // The guard waits in place, facing the same way, and retries next tick.
+    Block,
+    // The guard trades places with a guard that is standing still or
+    // walking straight at it.
+    Swap,
+    // Guards meeting on a tile, or crossing head-on, continue as the
+    // earliest of them.
+    Merge,
+}
+
+impl std::str::FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Collision::Block),
+            "swap" => Ok(Collision::Swap),
+            "merge" => Ok(Collision::Merge),
+            _ => Err(format!("Unknown collision rule '{}', expected block, swap or merge", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatrolOutcome {
+    Exited { ticks: usize, visited: HashSet<Position> },
+    Looped { loop_start: usize, loop_length: usize },
+    // Guards with behaviour state of their own were still walking after as
+    // many ticks as all of them could walk one after another without it.
+    Undecided { ticks: usize },
+}
+
+// Position, direction, walls broken and behaviour state of one guard.
+type GuardState = (Position, Direction, usize, Option<u64>);
+
+// Moves every guard of a map one step per tick. Guards that leave the map or
+// merge into another are gone for good. Each guard breaks cracked walls only
+// for itself.
+pub struct Patrol<B> {
+    rule: Collision,
+    behaviour: B,
+}
+
+impl<B: GuardBehaviour + Clone> Patrol<B> {
+    pub fn new(rule: Collision, behaviour: B) -> Patrol<B> {
+        Patrol { rule, behaviour }
+    }
+
+    pub fn run(&self, map: &Map) -> PatrolOutcome {
+        let mut guards = self.walkers(map);
+        let mut visited: HashSet<Position> = guards.iter().flatten().map(Walker::position).collect();
+        let mut seen = HashMap::from([(Self::joint_state(&guards), 0)]);
+
+        // Without behaviour state guards only ever hold a position, a
+        // direction and how many walls they broke, so the joint state has to
+        // repeat eventually. With it there is no telling, so give up at some
+        // point.
+        let limit = self.behaviour.state()
+            .and(guards.iter().flatten().next())
+            .map(|walker| guards.len() * walker.state_limit());
+        let mut tick = 0;
+        loop {
+            if guards.iter().all(Option::is_none) {
+                return PatrolOutcome::Exited { ticks: tick, visited };
+            }
+            if limit.is_some_and(|limit| tick >= limit) {
+                return PatrolOutcome::Undecided { ticks: tick };
+            }
+            tick += 1;
+            self.tick(&mut guards);
+            visited.extend(guards.iter().flatten().map(Walker::position));
+
+            let state = Self::joint_state(&guards);
+            if let Some(&first_seen) = seen.get(&state) {
+                return PatrolOutcome::Looped { loop_start: first_seen, loop_length: tick - first_seen };
+            }
+            seen.insert(state, tick);
+        }
+    }
+
+    fn walkers<'m>(&self, map: &'m Map) -> Vec<Option<Walker<'m, B>>> {
+        map.guards().into_iter()
+            .map(|(position, guard)| Some(Walker::at(map, position, *guard.direction(), self.behaviour.clone())))
+            .collect()
+    }
+
+    fn joint_state(guards: &[Option<Walker<B>>]) -> Vec<Option<GuardState>> {
+        guards.iter()
+            .map(|guard| guard.as_ref().map(|walker| {
+                (walker.position(), walker.direction(), walker.broken().len(), walker.behaviour().state())
+            }))
+            .collect()
+    }
+
+    fn tick(&self, guards: &mut [Option<Walker<B>>]) {
+        let mut moves: Vec<Option<(Position, Direction)>> = vec![None; guards.len()];
+        for (guard, next) in guards.iter_mut().zip(moves.iter_mut()) {
+            let Some(walker) = guard else {
+                continue;
+            };
+            match walker.next_step() {
+                Step::Exit => *guard = None,
+                Step::Turn { .. } => {},
+                Step::Move { to, direction } => *next = Some((to, direction)),
+            }
+        }
+
+        let positions: Vec<Option<Position>> = guards.iter().map(|guard| guard.as_ref().map(Walker::position)).collect();
+        let targets: Vec<Option<Position>> = moves.iter().map(|next| next.map(|(to, _)| to)).collect();
+        let finals = match self.rule {
+            Collision::Merge => Self::merge(&positions, &targets),
+            Collision::Block | Collision::Swap => self.resolve(&positions, &targets),
+        };
+
+        for ((guard, position), next) in guards.iter_mut().zip(finals).zip(moves) {
+            match (guard.as_mut(), position) {
+                (Some(walker), Some(position)) => {
+                    let direction = match next {
+                        Some((to, direction)) if to == position => direction,
+                        _ => walker.direction(),
+                    };
+                    walker.advance(position, direction);
+                },
+                _ => *guard = None,
+            }
+        }
+    }
+
+    fn head_on(positions: &[Option<Position>], targets: &[Option<Position>], i: usize, j: usize) -> bool {
+        targets[i].is_some() && targets[i] == positions[j] && targets[j] == positions[i]
+    }
+
+    fn merge(positions: &[Option<Position>], targets: &[Option<Position>]) -> Vec<Option<Position>> {
+        let mut finals: Vec<Option<Position>> = positions.iter().zip(targets).map(|(p, t)| t.or(*p)).collect();
+        for i in 0..finals.len() {
+            for j in i + 1..finals.len() {
+                if Self::head_on(positions, targets, i, j) {
+                    finals[j] = finals[i];
+                }
+            }
+        }
+
+        let mut taken = HashSet::new();
+        for position in finals.iter_mut() {
+            if let Some(p) = position {
+                if !taken.insert(*p) {
+                    *position = None;
+                }
+            }
+        }
+        finals
+    }
+
+    fn resolve(&self, positions: &[Option<Position>], targets: &[Option<Position>]) -> Vec<Option<Position>> {
+        let n = positions.len();
+        let mut finals: Vec<Option<Position>> = positions.iter().zip(targets).map(|(p, t)| t.or(*p)).collect();
+        let mut partner: Vec<Option<usize>> = vec![None; n];
+
+        for i in 0..n {
+            let Some(j) = (0..n).find(|&j| j != i && targets[i].is_some() && positions[j] == targets[i]) else {
+                continue;
+            };
+            let facing_off = Self::head_on(positions, targets, i, j);
+            match self.rule {
+                Collision::Swap if (targets[j].is_none() || facing_off) && partner[i].is_none() && partner[j].is_none() => {
+                    partner[i] = Some(j);
+                    partner[j] = Some(i);
+                    finals[j] = positions[i];
+                },
+                _ if facing_off && partner[i].is_none() => {
+                    finals[i] = positions[i];
+                    finals[j] = positions[j];
+                },
+                _ => {},
+            }
+        }
+
+        // Cancelling a move can put a guard back on a tile someone else was
+        // about to step into, so repeat until nothing changes.
+        loop {
+            let mut changed = false;
+            for i in 0..n {
+                if finals[i].is_none() || finals[i] == positions[i] {
+                    continue;
+                }
+                let contested = (0..n).any(|k| {
+                    k != i && finals[k] == finals[i] && (finals[k] == positions[k] || k < i)
+                });
+                if contested {
+                    finals[i] = positions[i];
+                    if let Some(p) = partner[i].take() {
+                        finals[p] = positions[p];
+                        partner[p] = None;
+                    }
+                    changed = true;
+                }
+            }
+            if !changed {
+                return finals;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::{RandomTurns, TurnAround, TurnRight};
+    use crate::EXAMPLE;
+
+    fn run(input: &str, rule: Collision) -> PatrolOutcome {
+        Patrol::new(rule, TurnRight).run(&Map::parse(input).unwrap())
+    }
+
+    #[test]
+    fn test_single_guard() {
+        let PatrolOutcome::Exited { ticks, visited } = run(EXAMPLE, Collision::Block) else {
+            panic!("Guard should leave");
+        };
+        assert_eq!(ticks, 55);
+        assert_eq!(visited.len(), 41);
+    }
+
+    #[test]
+    fn test_collision_rules() {
+        let corridor = "#>.<#";
+
+        assert_eq!(run(corridor, Collision::Block), PatrolOutcome::Looped { loop_start: 1, loop_length: 1 });
+        assert!(matches!(run(corridor, Collision::Swap), PatrolOutcome::Exited { ticks: 5, .. }));
+        assert!(matches!(run(corridor, Collision::Merge), PatrolOutcome::Exited { ticks: 4, .. }));
+    }
+
+    #[test]
+    fn test_swap_with_standing_guard() {
+        // The lower guard turns in place while the upper one walks into it.
+        let map = Map::parse("...\n.v.\n.>#\n...").unwrap();
+        let positions = |rule| {
+            let patrol = Patrol::new(rule, TurnRight);
+            let mut guards = patrol.walkers(&map);
+            patrol.tick(&mut guards);
+            guards.into_iter().map(|guard| guard.map(|walker| (walker.position(), walker.direction()))).collect::<Vec<_>>()
+        };
+
+        assert_eq!(positions(Collision::Swap), [Some(((2, 1), Direction::Down)), Some(((1, 1), Direction::Down))]);
+        assert_eq!(positions(Collision::Block), [Some(((1, 1), Direction::Down)), Some(((2, 1), Direction::Down))]);
+        assert_eq!(positions(Collision::Merge), [Some(((2, 1), Direction::Down)), None]);
+    }
+
+    #[test]
+    fn test_joint_loop() {
+        let map = Map::parse("#>.##\n#>..#").unwrap();
+
+        assert_eq!(
+            Patrol::new(Collision::Block, TurnAround).run(&map),
+            PatrolOutcome::Looped { loop_start: 0, loop_length: 12 },
+        );
+    }
+
+    #[test]
+    fn test_special_tiles() {
+        // The arrow turns the guard down instead of letting it walk on.
+        let PatrolOutcome::Exited { ticks, visited } = run(">↓.\n...", Collision::Block) else {
+            panic!("Guard should leave");
+        };
+        assert_eq!(ticks, 3);
+        assert_eq!(visited, HashSet::from([(0, 0), (0, 1), (1, 1)]));
+    }
+
+    #[test]
+    fn test_random_turns_terminate() {
+        // Boxed in, the generator never repeats, so the patrol gives up
+        // instead of calling it a loop.
+        let boxed = Map::parse("###\n#^#\n###").unwrap();
+        for seed in 1..20 {
+            let outcome = Patrol::new(Collision::Block, RandomTurns::new(seed)).run(&boxed);
+            assert_eq!(outcome, PatrolOutcome::Undecided { ticks: 9 * 8 });
+        }
+
+        // Two guards that end up facing each other block each other without
+        // turning, which is a loop.
+        let map = Map::parse("####\n#^v#\n####").unwrap();
+        for seed in 1..20 {
+            let outcome = Patrol::new(Collision::Block, RandomTurns::new(seed)).run(&map);
+            assert!(matches!(outcome, PatrolOutcome::Looped { loop_length: 1, .. }));
+        }
+
+        let open = Map::parse("###.#\n#...#\n#^..#\n#####").unwrap();
+        for seed in 1..200 {
+            let outcome = Patrol::new(Collision::Block, RandomTurns::new(seed)).run(&open);
+            assert!(matches!(outcome, PatrolOutcome::Exited { .. }), "seed {}: {:?}", seed, outcome);
+        }
+        assert!(matches!(Patrol::new(Collision::Swap, RandomTurns::new(3)).run(&Map::parse(EXAMPLE).unwrap()), PatrolOutcome::Exited { .. }));
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::behaviour::TurnRight;
+use crate::guard::{Direction, Guard};
+use crate::map::{Map, MapError, Object, Position};
+use crate::walker::{Step, Walker};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Moved { from: Position, to: Position, direction: Direction },
+    Turned { at: Position, from: Direction, to: Direction },
+    Exited { from: Position, direction: Direction },
+    // The guard is back in a state first reached at `first_seen`.
+    LoopDetected { at: Position, direction: Direction, first_seen: usize },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Moved { to, .. } => write!(f, "moved to {:?}", to),
+            Event::Turned { at, to, .. } => write!(f, "turned {:?} at {:?}", to, at),
+            Event::Exited { from, direction } => write!(f, "exited {:?} from {:?}", direction, from),
+            Event::LoopDetected { at, direction, first_seen } => {
+                write!(f, "loop: back at {:?} facing {:?}, first seen at step {}", at, direction, first_seen)
+            },
+        }
+    }
+}
+
+// Walks the guard one event at a time. Step 0 is the starting state, and the
+// n-th event yielded leads into step n.
+pub struct GuardSimulation<'m> {
+    walker: Walker<'m, TurnRight>,
+    // Position, direction and number of broken walls at every step.
+    history: Vec<(Position, Direction, usize)>,
+    seen: HashMap<(Position, Direction, usize), usize>,
+    finished: bool,
+}
+
+impl<'m> GuardSimulation<'m> {
+    pub fn new(map: &'m Map) -> GuardSimulation<'m> {
+        GuardSimulation::from_walker(Walker::new(map, TurnRight))
+    }
+
+    pub fn with_obstacle(map: &'m Map, obstacle: Position) -> Result<GuardSimulation<'m>, MapError> {
+        Ok(GuardSimulation::from_walker(Walker::new(map, TurnRight).with_obstacle(obstacle)?))
+    }
+
+    fn from_walker(walker: Walker<'m, TurnRight>) -> GuardSimulation<'m> {
+        let state = (walker.position(), walker.direction(), 0);
+        GuardSimulation {
+            walker,
+            history: vec![state],
+            seen: HashMap::from([(state, 0)]),
+            finished: false,
+        }
+    }
+
+    pub fn step(&self) -> usize {
+        self.history.len() - 1
+    }
+
+    // Goes back to `step`, forgetting everything after it. Returns false if
+    // the step was never reached.
+    pub fn rewind(&mut self, step: usize) -> bool {
+        if step > self.step() {
+            return false;
+        }
+        self.history.truncate(step + 1);
+        self.seen.retain(|_, first_seen| *first_seen <= step);
+        let (position, direction, broken) = self.history[step];
+        self.walker.reset(position, direction, broken);
+        self.finished = false;
+        true
+    }
+
+    // The map as it looked at `step`, with the guard standing where it was.
+    pub fn snapshot(&self, step: usize) -> Option<Snapshot> {
+        let &(position, direction, broken) = self.history.get(step)?;
+        let map = self.walker.map();
+        let broken = &self.walker.broken()[..broken];
+
+        let mut tiles = vec![Vec::new(); map.size().0];
+        for (row, col, object) in map.tiles() {
+            let tile = (row as isize, col as isize);
+            tiles[row].push(match object {
+                _ if Some(tile) == self.walker.obstacle() => Object::Wall,
+                _ if broken.contains(&tile) || tile == map.guard().0 => Object::Empty,
+                object => object.clone(),
+            });
+        }
+        Some(Snapshot { tiles, guard: (position, direction) })
+    }
+}
+
+// The tiles at one step of a walk. The guard is kept apart from them, so a
+// portal or arrow it stands on is still there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    tiles: Vec<Vec<Object>>,
+    guard: (Position, Direction),
+}
+
+impl Snapshot {
+    pub fn get(&self, position: &Position) -> Option<&Object> {
+        let row = self.tiles.get(usize::try_from(position.0).ok()?)?;
+        row.get(usize::try_from(position.1).ok()?)
+    }
+
+    pub fn guard(&self) -> (Position, Direction) {
+        self.guard
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (row_i, row) in self.tiles.iter().enumerate() {
+            if row_i > 0 {
+                writeln!(f)?;
+            }
+            for (col_i, object) in row.iter().enumerate() {
+                if (row_i as isize, col_i as isize) == self.guard.0 {
+                    write!(f, "{}", Object::Guard(Guard::new(self.guard.1)).symbol())?;
+                } else {
+                    write!(f, "{}", object.symbol())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for GuardSimulation<'_> {
+    type Item = (usize, Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let step = self.history.len();
+        let (position, direction) = (self.walker.position(), self.walker.direction());
+
+        let event = match self.walker.step() {
+            Step::Exit => {
+                self.finished = true;
+                return Some((step, Event::Exited { from: position, direction }));
+            },
+            Step::Turn { from, to } => Event::Turned { at: position, from, to },
+            Step::Move { to, direction } => Event::Moved { from: position, to, direction },
+        };
+
+        let state = (self.walker.position(), self.walker.direction(), self.walker.broken().len());
+        if let Some(&first_seen) = self.seen.get(&state) {
+            self.finished = true;
+            return Some((step, Event::LoopDetected { at: state.0, direction: state.1, first_seen }));
+        }
+        self.seen.insert(state, step);
+        self.history.push(state);
+        Some((step, event))
+    }
+}
+
+const HELP: &str = "Commands: n [count] (next events), r <step> (rewind), p (print map), q (quit)";
+
+// Steps through a walk by hand, going back and forth between steps.
+pub fn step_through(mut simulation: GuardSimulation, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    writeln!(output, "{}", HELP)?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number: Option<usize> = words.get(1).and_then(|word| word.parse().ok());
+
+        match (words.first().copied(), number) {
+            (Some("n"), count) => {
+                for (step, event) in simulation.by_ref().take(count.unwrap_or(1)) {
+                    writeln!(output, "{:>6} {}", step, event)?;
+                }
+            },
+            (Some("r"), Some(step)) if simulation.rewind(step) => {},
+            (Some("r"), Some(step)) => writeln!(output, "Step {} was never reached", step)?,
+            (Some("p"), _) => {
+                if let Some(snapshot) = simulation.snapshot(simulation.step()) {
+                    let (position, direction) = snapshot.guard();
+                    writeln!(output, "{}", snapshot)?;
+                    if let Some(tile) = snapshot.get(&position) {
+                        writeln!(output, "Guard at {:?} facing {:?} on '{}'", position, direction, tile.symbol())?;
+                    }
+                }
+            },
+            (Some("q"), _) => break,
+            (None, _) => continue,
+            _ => writeln!(output, "{}", HELP)?,
+        }
+        writeln!(output, "At step {}", simulation.step())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiles::{Arrow, Portal, Tile};
+    use crate::EXAMPLE;
+
+    #[test]
+    fn test_events() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut simulation = GuardSimulation::new(&map);
+
+        assert_eq!(simulation.next(), Some((1, Event::Moved { from: (6, 4), to: (5, 4), direction: Direction::Up })));
+        let events: Vec<_> = simulation.by_ref().collect();
+        assert_eq!(events[4], (6, Event::Turned { at: (1, 4), from: Direction::Up, to: Direction::Right }));
+        assert_eq!(events.last(), Some(&(55, Event::Exited { from: (9, 7), direction: Direction::Down })));
+        assert_eq!(simulation.next(), None);
+    }
+
+    #[test]
+    fn test_special_tiles() {
+        let map = Map::parse(".A..\n....\n...A\n...^").unwrap();
+        let events: Vec<_> = GuardSimulation::new(&map).map(|(_, event)| event).collect();
+
+        assert_eq!(events, [
+            Event::Moved { from: (3, 3), to: (0, 1), direction: Direction::Up },
+            Event::Exited { from: (0, 1), direction: Direction::Up },
+        ]);
+    }
+
+    #[test]
+    fn test_loop_detected() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let simulation = GuardSimulation::with_obstacle(&map, (6, 3)).unwrap();
+
+        let (_, last) = simulation.last().unwrap();
+        assert!(matches!(last, Event::LoopDetected { at: (6, 4), direction: Direction::Up, first_seen: 0 }));
+    }
+
+    #[test]
+    fn test_rewind_replays_same_events() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut simulation = GuardSimulation::new(&map);
+        let events: Vec<_> = simulation.by_ref().collect();
+
+        assert!(!simulation.rewind(100));
+        assert!(simulation.rewind(10));
+        assert_eq!(simulation.step(), 10);
+        let replayed: Vec<_> = simulation.collect();
+        assert_eq!(replayed, events[10..]);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let mut simulation = GuardSimulation::new(&map);
+        simulation.by_ref().take(6).for_each(drop);
+
+        let snapshot = simulation.snapshot(6).unwrap();
+        assert_eq!(snapshot.guard(), ((1, 4), Direction::Right));
+        assert_eq!(snapshot.get(&(6, 4)), Some(&Object::Empty));
+        assert_eq!(simulation.snapshot(0).unwrap().to_string(), EXAMPLE);
+        assert_eq!(simulation.snapshot(7), None);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_tile_under_guard() {
+        let map = Map::parse(".A..\n....\n...A\n...^").unwrap();
+        let mut simulation = GuardSimulation::new(&map);
+        simulation.next();
+
+        let snapshot = simulation.snapshot(1).unwrap();
+        assert_eq!(snapshot.guard(), ((0, 1), Direction::Up));
+        assert_eq!(snapshot.get(&(0, 1)), Some(&Object::Tile(Tile::new(Portal('A')))));
+        assert_eq!(snapshot.get(&(3, 3)), Some(&Object::Empty));
+
+        let map = Map::parse("...\n→..\n^..").unwrap();
+        let mut simulation = GuardSimulation::new(&map);
+        simulation.next();
+
+        let snapshot = simulation.snapshot(1).unwrap();
+        assert_eq!(snapshot.guard(), ((1, 0), Direction::Right));
+        assert_eq!(snapshot.get(&(1, 0)), Some(&Object::Tile(Tile::new(Arrow(Direction::Right)))));
+    }
+
+    #[test]
+    fn test_step_through() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let commands = "n 6\nr 2\nn\nr 9\np\nq\nn\n";
+
+        let mut output = Vec::new();
+        step_through(GuardSimulation::new(&map), commands.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("     6 turned Right at (1, 4)\nAt step 6\nAt step 2\n     3 moved to (3, 4)\nAt step 3\n"));
+        assert!(output.contains("Step 9 was never reached"));
+        assert!(output.contains("..#.^.....\n"));
+        assert!(output.contains("Guard at (3, 4) facing Up on '.'"));
+        assert!(output.ends_with("At step 3\n"));
+    }
+}
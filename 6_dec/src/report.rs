@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::behaviour::TurnRight;
+use crate::guard::Direction;
+use crate::map::{Map, Position};
+
+// One obstruction that traps the guard, and the loop it traps the guard in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementReport {
+    pub obstruction: Position,
+    // Step of the original walk at which the guard first stands on the tile.
+    pub first_reached: usize,
+    // Number of states in the cycle, counting turns on the spot as steps.
+    pub loop_length: usize,
+    pub loop_tiles: HashSet<Position>,
+    loop_states: Vec<(Position, Direction)>,
+}
+
+impl Map {
+    // Loop placements ordered by when the guard would first run into them.
+    pub fn loop_placement_report(&self, threads: usize) -> Vec<PlacementReport> {
+        let (states, _) = self.walk();
+        let mut first_reached: HashMap<Position, usize> = HashMap::new();
+        for (step, (position, _)) in states.iter().enumerate() {
+            first_reached.entry(*position).or_insert(step);
+        }
+
+        let mut reports: Vec<PlacementReport> = self.placement_cycles_with(&TurnRight, threads)
+            .into_iter()
+            .map(|(obstruction, loop_states)| PlacementReport {
+                obstruction,
+                first_reached: first_reached[&obstruction],
+                loop_length: loop_states.len(),
+                loop_tiles: loop_states.iter().map(|(position, _)| *position).collect(),
+                loop_states,
+            })
+            .collect();
+        reports.sort_by_key(|report| (report.first_reached, report.obstruction));
+        reports
+    }
+}
+
+impl PlacementReport {
+    pub fn render(&self, map: &Map) -> String {
+        render_path(map, &self.loop_states, Some(self.obstruction))
+    }
+}
+
+// Draws the map like the puzzle does: `|` and `-` where the guard walks
+// straight, `+` where it turns or crosses its own path, and `O` for an
+// extra obstruction.
+pub fn render_path(map: &Map, states: &[(Position, Direction)], obstruction: Option<Position>) -> String {
+    let mut vertical: HashSet<Position> = HashSet::new();
+    let mut horizontal: HashSet<Position> = HashSet::new();
+    for (position, direction) in states {
+        match direction {
+            Direction::Up | Direction::Down => vertical.insert(*position),
+            _ => horizontal.insert(*position),
+        };
+    }
+
+    let (rows, cols) = map.size();
+    let mut render = String::new();
+    for row in 0..rows {
+        for col in 0..cols {
+            let position = (row as isize, col as isize);
+            let object = map.get(&position).unwrap();
+            let symbol = match (vertical.contains(&position), horizontal.contains(&position)) {
+                _ if Some(position) == obstruction => 'O',
+                _ if position == map.guard().0 => object.symbol(),
+                (true, true) => '+',
+                (true, false) => '|',
+                (false, true) => '-',
+                (false, false) => object.symbol(),
+            };
+            render.push(symbol);
+        }
+        render.push('\n');
+    }
+    render
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EXAMPLE;
+
+    #[test]
+    fn test_report() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let reports = map.loop_placement_report(1);
+
+        let obstructions: Vec<Position> = reports.iter().map(|report| report.obstruction).collect();
+        assert_eq!(obstructions.len(), 6);
+        assert!(reports.windows(2).all(|pair| pair[0].first_reached <= pair[1].first_reached));
+
+        let first = reports.iter().find(|report| report.obstruction == (6, 3)).unwrap();
+        assert_eq!(first.first_reached, 22);
+        assert_eq!(first.loop_tiles.len(), 18);
+        assert!(first.loop_tiles.contains(&(1, 4)));
+    }
+
+    #[test]
+    fn test_report_special_tiles() {
+        // Same loops with a portal pair the guard never reaches.
+        let map = Map::parse(&EXAMPLE.replacen('.', "A", 1).replacen("......#...", "A.....#...", 1)).unwrap();
+        let plain = Map::parse(EXAMPLE).unwrap().loop_placement_report(2);
+
+        assert_eq!(map.loop_placement_report(2), plain);
+    }
+
+    #[test]
+    fn test_render() {
+        let map = Map::parse(EXAMPLE).unwrap();
+        let report = map.loop_placement_report(1).into_iter().find(|report| report.obstruction == (6, 3)).unwrap();
+
+        assert_eq!(report.render(&map),
+"....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.O^---+.
+........#.
+#.........
+......#...
+");
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use crate::map::{Map, Object};
+
+const EXIT: u32 = u32::MAX;
+
+// Indices of `Direction::CARDINAL`.
+const UP: usize = 0;
+const RIGHT: usize = 1;
+const DOWN: usize = 2;
+const LEFT: usize = 3;
+
+pub type Cell = usize;
+
+// Dense form of a `Map` for fast walking. `jump[cell * 4 + dir]` is the last
+// free cell the guard reaches walking straight from `cell`, or `EXIT` when
+// nothing stops the guard before leaving the map.
+pub struct Simulation {
+    width: usize,
+    height: usize,
+    walls: Vec<bool>,
+    jump: Vec<u32>,
+    start: Cell,
+    start_direction: usize,
+}
+
+impl Simulation {
+    // Only maps of plain tiles with a guard facing a cardinal direction can
+    // be turned into jump tables.
+    pub fn new(map: &Map) -> Option<Simulation> {
+        let (guard_pos, guard) = map.guard();
+        let start_direction = guard.direction().index()?;
+        let (height, width) = map.size();
+        let mut walls = vec![false; width * height];
+        for (row, col, object) in map.tiles() {
+            if !object.is_plain() {
+                return None;
+            }
+            walls[row * width + col] = *object == Object::Wall;
+        }
+        let start = guard_pos.0 as usize * width + guard_pos.1 as usize;
+
+        let mut simulation = Simulation {
+            width, height, walls, jump: vec![EXIT; width * height * 4], start, start_direction,
+        };
+        simulation.build_jump_table();
+        Some(simulation)
+    }
+
+    fn build_jump_table(&mut self) {
+        let (width, height) = (self.width, self.height);
+        // For each direction, sweep every line from the far end towards the
+        // guard's heading so the stop cell can be carried along.
+        for row in 0..height {
+            let mut stop = EXIT;
+            for col in 0..width {
+                self.sweep(row * width + col, LEFT, &mut stop, col + 1 < width, 1);
+            }
+            let mut stop = EXIT;
+            for col in (0..width).rev() {
+                self.sweep(row * width + col, RIGHT, &mut stop, col > 0, -1);
+            }
+        }
+        for col in 0..width {
+            let mut stop = EXIT;
+            for row in 0..height {
+                self.sweep(row * width + col, UP, &mut stop, row + 1 < height, width as isize);
+            }
+            let mut stop = EXIT;
+            for row in (0..height).rev() {
+                self.sweep(row * width + col, DOWN, &mut stop, row > 0, -(width as isize));
+            }
+        }
+    }
+
+    fn sweep(&mut self, cell: Cell, direction: usize, stop: &mut u32, has_next: bool, towards_next: isize) {
+        if self.walls[cell] {
+            *stop = if has_next { (cell as isize + towards_next) as u32 } else { EXIT };
+        } else {
+            self.jump[cell * 4 + direction] = *stop;
+        }
+    }
+
+    pub fn start(&self) -> (Cell, usize) {
+        (self.start, self.start_direction)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn position(&self, cell: Cell) -> (isize, isize) {
+        ((cell / self.width) as isize, (cell % self.width) as isize)
+    }
+
+    // The neighbouring cell in `direction`, if it is on the map.
+    pub fn next(&self, cell: Cell, direction: usize) -> Option<Cell> {
+        let (row, col) = (cell / self.width, cell % self.width);
+        match direction {
+            UP if row > 0 => Some(cell - self.width),
+            RIGHT if col + 1 < self.width => Some(cell + 1),
+            DOWN if row + 1 < self.height => Some(cell + self.width),
+            LEFT if col > 0 => Some(cell - 1),
+            _ => None,
+        }
+    }
+
+    fn distance(&self, from: Cell, to: Cell) -> usize {
+        let (a, b) = (self.position(from), self.position(to));
+        ((a.0 - b.0).abs() + (a.1 - b.1).abs()) as usize
+    }
+
+    // Whether `obstacle` lies on the straight line ahead of `cell`.
+    fn ahead(&self, cell: Cell, direction: usize, obstacle: Cell) -> bool {
+        let (from, to) = (self.position(cell), self.position(obstacle));
+        match direction {
+            UP => to.1 == from.1 && to.0 < from.0,
+            RIGHT => to.0 == from.0 && to.1 > from.1,
+            DOWN => to.1 == from.1 && to.0 > from.0,
+            _ => to.0 == from.0 && to.1 < from.1,
+        }
+    }
+
+    // Where the guard stops walking straight from `cell`, taking an extra
+    // obstacle into account without touching the jump table.
+    pub fn jump(&self, cell: Cell, direction: usize, obstacle: Option<Cell>) -> Option<Cell> {
+        let target = self.jump[cell * 4 + direction];
+        if let Some(obstacle) = obstacle.filter(|&o| self.ahead(cell, direction, o)) {
+            let blocks = target == EXIT || self.distance(cell, obstacle) <= self.distance(cell, target as usize);
+            if blocks {
+                let back = (direction + 2) % 4;
+                return self.next(obstacle, back);
+            }
+        }
+        (target != EXIT).then_some(target as usize)
+    }
+
+    // Follows turning points from a state until the guard exits (`false`) or
+    // repeats a turn (`true`). `seen` holds the generation a state was last
+    // visited in, so it never has to be cleared between runs.
+    pub fn loops_from(&self, mut cell: Cell, mut direction: usize, obstacle: Option<Cell>, seen: &mut [u32], generation: u32) -> bool {
+        while let Some(stop) = self.jump(cell, direction, obstacle) {
+            cell = stop;
+            direction = (direction + 1) % 4;
+            let state = cell * 4 + direction;
+            if seen[state] == generation {
+                return true;
+            }
+            seen[state] = generation;
+        }
+        false
+    }
+
+    // Step-by-step walk from the start. Returns every (cell, direction) state
+    // in order and, if the walk ended in a loop, the index of the state the
+    // guard came back to.
+    pub fn walk(&self, obstacle: Option<Cell>) -> (Vec<(Cell, usize)>, Option<usize>) {
+        let mut seen = vec![usize::MAX; self.cell_count() * 4];
+        let (mut cell, mut direction) = self.start();
+        let mut states = vec![(cell, direction)];
+        seen[cell * 4 + direction] = 0;
+
+        while let Some(state) = self.step(cell, direction, obstacle) {
+            (cell, direction) = state;
+            let first_seen = seen[cell * 4 + direction];
+            if first_seen != usize::MAX {
+                return (states, Some(first_seen));
+            }
+            seen[cell * 4 + direction] = states.len();
+            states.push((cell, direction));
+        }
+        (states, None)
+    }
+
+    fn step(&self, cell: Cell, direction: usize, obstacle: Option<Cell>) -> Option<(Cell, usize)> {
+        let next = self.next(cell, direction)?;
+        if self.walls[next] || Some(next) == obstacle {
+            Some((cell, (direction + 1) % 4))
+        } else {
+            Some((next, direction))
+        }
+    }
+
+    // Cells on the guard's original path where a new obstacle traps the guard in
+    // a loop. Each candidate is simulated from the state right before the
+    // guard would first walk into it, since nothing earlier changes.
+    pub fn loop_placements(&self, threads: usize) -> Vec<Cell> {
+        self.search(threads, &|candidate, _, _| Some(candidate))
+    }
+
+    // The same placements, each with every state of the loop it causes.
+    // Like `walk`, each loop starts at the state where the guard enters it.
+    pub fn loop_cycles(&self, threads: usize) -> Vec<(Cell, Vec<(Cell, usize)>)> {
+        let (path, _) = self.walk(None);
+        let mut first_seen = vec![usize::MAX; self.cell_count() * 4];
+        for (i, &(cell, direction)) in path.iter().enumerate() {
+            first_seen[cell * 4 + direction] = i;
+        }
+
+        self.search(threads, &|candidate, from, direction| {
+            let mut cycle = self.cycle(from, direction, candidate)?;
+            let index: HashMap<(Cell, usize), usize> = cycle.iter().enumerate().map(|(i, &state)| (state, i)).collect();
+
+            // The guard may already have been on the loop before it reached
+            // `from`, otherwise it walks on until it joins the loop.
+            let reached = first_seen[from * 4 + direction];
+            let entry = match cycle.iter().map(|&(cell, direction)| first_seen[cell * 4 + direction]).filter(|&i| i <= reached).min() {
+                Some(i) => index[&path[i]],
+                None => {
+                    let mut state = (from, direction);
+                    while !index.contains_key(&state) {
+                        state = self.step(state.0, state.1, Some(candidate))?;
+                    }
+                    index[&state]
+                },
+            };
+            cycle.rotate_left(entry);
+            Some((candidate, cycle))
+        })
+    }
+
+    fn search<T: Send>(&self, threads: usize, found: &(impl Fn(Cell, Cell, usize) -> Option<T> + Sync)) -> Vec<T> {
+        let candidates = self.placement_candidates();
+        // More threads than cores or candidates only adds overhead.
+        let available = std::thread::available_parallelism().map_or(1, usize::from);
+        self.search_among(&candidates, threads.min(available).min(candidates.len()), found)
+    }
+
+    fn search_among<T: Send>(&self, candidates: &[(Cell, Cell, usize)], threads: usize, found: &(impl Fn(Cell, Cell, usize) -> Option<T> + Sync)) -> Vec<T> {
+        if threads <= 1 {
+            return self.loops_among(candidates, found);
+        }
+
+        let chunk = candidates.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = candidates.chunks(chunk)
+                .map(|chunk| scope.spawn(move || self.loops_among(chunk, found)))
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        })
+    }
+
+    fn loops_among<T>(&self, candidates: &[(Cell, Cell, usize)], found: impl Fn(Cell, Cell, usize) -> Option<T>) -> Vec<T> {
+        let mut seen = vec![0; self.cell_count() * 4];
+        let mut generation = 0;
+        let mut placements = Vec::new();
+        for &(candidate, from, direction) in candidates {
+            generation += 1;
+            if self.loops_from(from, direction, Some(candidate), &mut seen, generation) {
+                placements.extend(found(candidate, from, direction));
+            }
+        }
+        placements
+    }
+
+    // Every state of the loop the guard ends up in from `cell`, found from
+    // the turning points and filled in with the straight walks between them.
+    fn cycle(&self, mut cell: Cell, mut direction: usize, obstacle: Cell) -> Option<Vec<(Cell, usize)>> {
+        let mut turns = Vec::new();
+        let mut seen = HashMap::new();
+        let start = loop {
+            cell = self.jump(cell, direction, Some(obstacle))?;
+            direction = (direction + 1) % 4;
+            if let Some(&start) = seen.get(&(cell, direction)) {
+                break start;
+            }
+            seen.insert((cell, direction), turns.len());
+            turns.push((cell, direction));
+        };
+
+        let turns = &turns[start..];
+        let mut states = Vec::new();
+        for (i, &(cell, direction)) in turns.iter().enumerate() {
+            states.push((cell, direction));
+            let (stop, _) = turns[(i + 1) % turns.len()];
+            let mut current = cell;
+            while current != stop {
+                current = self.next(current, direction)?;
+                states.push((current, direction));
+            }
+        }
+        Some(states)
+    }
+
+    pub fn placement_candidates(&self) -> Vec<(Cell, Cell, usize)> {
+        let (states, _) = self.walk(None);
+        let mut first_visit = vec![false; self.cell_count()];
+        first_visit[self.start] = true;
+
+        let mut candidates = Vec::new();
+        for pair in states.windows(2) {
+            let ((from, direction), (to, _)) = (pair[0], pair[1]);
+            if to != from && !first_visit[to] {
+                first_visit[to] = true;
+                candidates.push((to, from, direction));
+            }
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EXAMPLE;
+
+    #[test]
+    fn test_jump() {
+        let simulation = Simulation::new(&Map::parse(EXAMPLE).unwrap()).unwrap();
+        let cell = |row: usize, col: usize| row * simulation.width + col;
+
+        assert_eq!(simulation.start(), (cell(6, 4), UP));
+        assert_eq!(simulation.jump(cell(6, 4), 0, None), Some(cell(1, 4)));
+        assert_eq!(simulation.jump(cell(1, 4), 1, None), Some(cell(1, 8)));
+        assert_eq!(simulation.jump(cell(7, 7), 2, None), None);
+        assert_eq!(simulation.jump(cell(6, 4), 0, Some(cell(3, 4))), Some(cell(4, 4)));
+        assert_eq!(simulation.jump(cell(6, 4), 0, Some(cell(0, 4))), Some(cell(1, 4)));
+        assert_eq!(simulation.jump(cell(7, 7), 2, Some(cell(9, 7))), Some(cell(8, 7)));
+    }
+
+    #[test]
+    fn test_only_plain_maps() {
+        assert!(Simulation::new(&Map::parse(EXAMPLE).unwrap()).is_some());
+        assert!(Simulation::new(&Map::parse("~.\n^.").unwrap()).is_none());
+    }
+
+    // Deterministic maze big enough to exercise long jumps.
+    fn generated_map(size: usize) -> String {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut lines = Vec::new();
+        for row in 0..size {
+            let line: String = (0..size).map(|col| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                if (row, col) == (size / 2, size / 2) {
+                    '^'
+                } else if state.is_multiple_of(11) {
+                    '#'
+                } else {
+                    '.'
+                }
+            }).collect();
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_loop_placements_match_walk() {
+        let simulation = Simulation::new(&Map::parse(&generated_map(60)).unwrap()).unwrap();
+
+        let mut fast = simulation.loop_placements(1);
+        let mut slow: Vec<Cell> = simulation.placement_candidates()
+            .into_iter()
+            .map(|(candidate, _, _)| candidate)
+            .filter(|&candidate| simulation.walk(Some(candidate)).1.is_some())
+            .collect();
+        fast.sort();
+        slow.sort();
+
+        assert!(!fast.is_empty());
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    fn test_loop_cycles_match_walk() {
+        let simulation = Simulation::new(&Map::parse(&generated_map(60)).unwrap()).unwrap();
+
+        for (candidate, cycle) in simulation.loop_cycles(1) {
+            let (states, loop_start) = simulation.walk(Some(candidate));
+            assert_eq!(cycle, states[loop_start.unwrap()..]);
+        }
+    }
+
+    #[test]
+    fn test_parallel_loop_placements() {
+        for map in [EXAMPLE.to_string(), generated_map(200)] {
+            let simulation = Simulation::new(&Map::parse(&map).unwrap()).unwrap();
+            let candidates = simulation.placement_candidates();
+            let single = simulation.loop_placements(1);
+
+            for threads in [2, 3, 8, 1000] {
+                let mut parallel = simulation.search_among(&candidates, threads, &|candidate, _, _| Some(candidate));
+                parallel.sort();
+                let mut expected = single.clone();
+                expected.sort();
+                assert_eq!(parallel, expected);
+            }
+            assert_eq!(simulation.loop_placements(1000).len(), single.len());
+        }
+    }
+}
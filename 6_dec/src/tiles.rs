@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::guard::Direction;
+use crate::map::Object;
+
+// How a tile treats a guard trying to step onto it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Walk,
+    // Acts as a wall; the guard turns where it stands.
+    Block,
+    // Acts as a wall once, then crumbles into an empty tile.
+    Break,
+    // The guard comes out of the other portal with the same letter.
+    Teleport(char),
+    // The guard steps on and faces the given direction.
+    Redirect(Direction),
+}
+
+// What a kind of tile, other than walls and empty floor, does to a guard.
+// New kinds only need to implement this and be registered with a
+// `TileRegistry`.
+pub trait TileKind: fmt::Debug + Send + Sync {
+    // The character the map is written with.
+    fn symbol(&self) -> char;
+
+    fn entry(&self, heading: Direction) -> Entry;
+
+    // Whether a guard standing here can turn. Where it can't, it bounces
+    // straight back off whatever stopped it.
+    fn grips(&self) -> bool {
+        true
+    }
+
+    // Tiles sharing a link come in pairs, like portals with the same letter.
+    fn link(&self) -> Option<char> {
+        None
+    }
+}
+
+// A shared handle on some kind of tile. Two tiles are the same if they look
+// and act the same.
+#[derive(Debug, Clone)]
+pub struct Tile(Arc<dyn TileKind>);
+
+impl Tile {
+    pub fn new(kind: impl TileKind + 'static) -> Tile {
+        Tile(Arc::new(kind))
+    }
+}
+
+impl std::ops::Deref for Tile {
+    type Target = dyn TileKind;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl PartialEq for Tile {
+    fn eq(&self, other: &Tile) -> bool {
+        self.symbol() == other.symbol()
+            && self.grips() == other.grips()
+            && self.link() == other.link()
+            && Direction::ALL.iter().all(|&heading| self.entry(heading) == other.entry(heading))
+    }
+}
+
+// Acts as a wall once, then crumbles.
+#[derive(Debug, Clone, Copy)]
+pub struct CrackedWall;
+
+impl TileKind for CrackedWall {
+    fn symbol(&self) -> char {
+        '%'
+    }
+
+    fn entry(&self, _: Direction) -> Entry {
+        Entry::Break
+    }
+}
+
+// Keeps the guard sliding.
+#[derive(Debug, Clone, Copy)]
+pub struct Ice;
+
+impl TileKind for Ice {
+    fn symbol(&self) -> char {
+        '~'
+    }
+
+    fn entry(&self, _: Direction) -> Entry {
+        Entry::Walk
+    }
+
+    fn grips(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Portal(pub char);
+
+impl TileKind for Portal {
+    fn symbol(&self) -> char {
+        self.0
+    }
+
+    fn entry(&self, _: Direction) -> Entry {
+        Entry::Teleport(self.0)
+    }
+
+    fn link(&self) -> Option<char> {
+        Some(self.0)
+    }
+}
+
+// One-way: the guard is turned the arrow's way, and walking against the
+// arrow is like walking into a wall.
+#[derive(Debug, Clone, Copy)]
+pub struct Arrow(pub Direction);
+
+impl TileKind for Arrow {
+    fn symbol(&self) -> char {
+        match self.0 {
+            Direction::Up => '↑',
+            Direction::Right => '→',
+            Direction::Down => '↓',
+            Direction::Left => '←',
+            _ => '*',
+        }
+    }
+
+    fn entry(&self, heading: Direction) -> Entry {
+        if heading == self.0.reverse() {
+            Entry::Block
+        } else {
+            Entry::Redirect(self.0)
+        }
+    }
+}
+
+impl Object {
+    pub fn entry(&self, heading: Direction) -> Entry {
+        match self {
+            Object::Wall => Entry::Block,
+            Object::Guard(_) | Object::Empty => Entry::Walk,
+            Object::Tile(tile) => tile.entry(heading),
+        }
+    }
+
+    pub fn grips(&self) -> bool {
+        match self {
+            Object::Tile(tile) => tile.grips(),
+            _ => true,
+        }
+    }
+
+    pub fn link(&self) -> Option<char> {
+        match self {
+            Object::Tile(tile) => tile.link(),
+            _ => None,
+        }
+    }
+
+    // The character the default registry reads as this tile.
+    pub fn symbol(&self) -> char {
+        match self {
+            Object::Guard(guard) => match guard.direction() {
+                Direction::Up => '^',
+                Direction::Right => '>',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+                _ => '*',
+            },
+            Object::Wall => '#',
+            Object::Empty => '.',
+            Object::Tile(tile) => tile.symbol(),
+        }
+    }
+
+    // Tiles the jump tables understand without falling back to `solve_with`.
+    pub fn is_plain(&self) -> bool {
+        !matches!(self, Object::Tile(_))
+    }
+}
+
+// Characters `Map::parse_with` understands, besides the guards `^ > v <`,
+// which always take precedence.
+#[derive(Debug, Clone)]
+pub struct TileRegistry {
+    symbols: HashMap<char, Object>,
+}
+
+impl Default for TileRegistry {
+    fn default() -> Self {
+        let mut registry = TileRegistry { symbols: HashMap::new() };
+        registry
+            .register('.', Object::Empty)
+            .register('#', Object::Wall)
+            .register_tile('%', CrackedWall)
+            .register_tile('~', Ice)
+            .register_tile('↑', Arrow(Direction::Up))
+            .register_tile('→', Arrow(Direction::Right))
+            .register_tile('↓', Arrow(Direction::Down))
+            .register_tile('←', Arrow(Direction::Left));
+        for letter in 'A'..='Z' {
+            registry.register_tile(letter, Portal(letter));
+        }
+        registry
+    }
+}
+
+impl TileRegistry {
+    // Adds a symbol, replacing whatever it meant before.
+    pub fn register(&mut self, symbol: char, object: Object) -> &mut Self {
+        self.symbols.insert(symbol, object);
+        self
+    }
+
+    pub fn register_tile(&mut self, symbol: char, kind: impl TileKind + 'static) -> &mut Self {
+        self.register(symbol, Object::Tile(Tile::new(kind)))
+    }
+
+    pub fn get(&self, symbol: char) -> Option<&Object> {
+        self.symbols.get(&symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Map, MapError, SimulationOutcome};
+    use crate::EXAMPLE;
+
+    fn solve(input: &str) -> SimulationOutcome {
+        Map::parse(input).unwrap().solve()
+    }
+
+    fn path_of(outcome: SimulationOutcome) -> Vec<(isize, isize)> {
+        match outcome {
+            SimulationOutcome::Exited { path } => {
+                let mut tiles: Vec<_> = path.into_keys().collect();
+                tiles.sort();
+                tiles
+            },
+            outcome => panic!("Expected the guard to exit, got {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_portals() {
+        // Up through A, out of the other A, and on up off the map.
+        assert_eq!(path_of(solve(".A..\n....\n...A\n...^")), [(0, 1), (3, 3)]);
+        assert_eq!(Map::parse("A.\n^B\nB."), Err(MapError::UnpairedPortal('A')));
+    }
+
+    #[test]
+    fn test_arrows() {
+        // The arrow turns the guard right; walking against it is a wall.
+        assert_eq!(path_of(solve("...\n→..\n^..")), [(1, 0), (1, 1), (1, 2), (2, 0)]);
+        assert_eq!(path_of(solve(">←.\n...")), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_ice_bounces() {
+        let SimulationOutcome::Exited { path } = solve("#.\n~.\n^.") else {
+            panic!("Guard should leave");
+        };
+        assert_eq!(path[&(1, 0)], [Direction::Up, Direction::Down]);
+        assert!(!path.contains_key(&(1, 1)));
+    }
+
+    #[test]
+    fn test_cracked_wall_breaks_once() {
+        let input = EXAMPLE.replace(".#..^", ".#.X^");
+
+        // A solid wall there traps the guard, a cracked one lets it through
+        // on the second lap.
+        assert!(matches!(solve(&input.replace('X', "#")), SimulationOutcome::Looped { .. }));
+        let SimulationOutcome::Exited { path } = solve(&input.replace('X', "%")) else {
+            panic!("Guard should break through");
+        };
+        assert!(path.contains_key(&(6, 3)));
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = TileRegistry::default();
+        registry.register('o', Object::Wall).register_tile('A', Ice);
+
+        let map = Map::parse_with("o.\nA^", &registry).unwrap();
+        assert_eq!(map.get(&(0, 0)), Some(&Object::Wall));
+        assert_eq!(map.get(&(1, 0)), Some(&Object::Tile(Tile::new(Ice))));
+        assert_ne!(map.get(&(1, 0)), Some(&Object::Tile(Tile::new(CrackedWall))));
+        assert_eq!(Map::parse("o.\n.^"), Err(MapError::InvalidCharacter { row: 0, col: 0, found: 'o' }));
+    }
+
+    // Only lets guards through going up or down.
+    #[derive(Debug)]
+    struct Gate;
+
+    impl TileKind for Gate {
+        fn symbol(&self) -> char {
+            '|'
+        }
+
+        fn entry(&self, heading: Direction) -> Entry {
+            match heading {
+                Direction::Up | Direction::Down => Entry::Walk,
+                _ => Entry::Block,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_tile() {
+        let mut registry = TileRegistry::default();
+        registry.register_tile('|', Gate);
+
+        // Up through the gate, then right into the side of the other one.
+        let map = Map::parse_with("#...\n|.|.\n^...", &registry).unwrap();
+        assert_eq!(path_of(map.solve()), [(1, 0), (1, 1), (2, 0), (2, 1)]);
+        assert_eq!(map.to_string(), "#...\n|.|.\n^...");
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use crate::behaviour::GuardBehaviour;
+use crate::guard::{Direction, Guard};
+use crate::map::{Map, MapError, Object, Position};
+use crate::tiles::Entry;
+
+// What one step does to a guard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    // Portals and arrows can put the guard somewhere other than the tile
+    // ahead, or turn it on the way.
+    Move { to: Position, direction: Direction },
+    Turn { from: Direction, to: Direction },
+    Exit,
+}
+
+// How a walk came to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkEnd {
+    Exited,
+    // Back in the state first reached at this index.
+    Looped(usize),
+    // Still walking after going through more states than a behaviour
+    // without state could before repeating one.
+    Undecided,
+}
+
+// A single guard walking a map under some behaviour. An extra obstacle and
+// the walls the guard breaks only exist for this walker, so trying a
+// placement never copies the map.
+#[derive(Debug, Clone)]
+pub struct Walker<'m, B> {
+    map: &'m Map,
+    obstacle: Option<Position>,
+    broken: Vec<Position>,
+    position: Position,
+    guard: Guard,
+    behaviour: B,
+}
+
+impl<'m, B: GuardBehaviour> Walker<'m, B> {
+    pub fn new(map: &'m Map, behaviour: B) -> Walker<'m, B> {
+        let (position, guard) = map.guard();
+        Walker::at(map, position, *guard.direction(), behaviour)
+    }
+
+    pub fn at(map: &'m Map, position: Position, direction: Direction, behaviour: B) -> Walker<'m, B> {
+        Walker {
+            map,
+            obstacle: None,
+            broken: Vec::new(),
+            position,
+            guard: Guard::new(direction),
+            behaviour,
+        }
+    }
+
+    // Treats `obstacle` as a wall. Refuses the same tiles `Map::place` does.
+    pub fn with_obstacle(mut self, obstacle: Position) -> Result<Walker<'m, B>, MapError> {
+        self.map.check_place(obstacle, &Object::Wall)?;
+        self.obstacle = Some(obstacle);
+        Ok(self)
+    }
+
+    pub fn map(&self) -> &'m Map {
+        self.map
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn direction(&self) -> Direction {
+        *self.guard.direction()
+    }
+
+    pub fn obstacle(&self) -> Option<Position> {
+        self.obstacle
+    }
+
+    // Cracked walls the guard has broken through, in the order it did.
+    pub fn broken(&self) -> &[Position] {
+        &self.broken
+    }
+
+    // Works out the guard's next step. Turns happen straight away, but a
+    // move only once `advance` is called, so the guard can be held back.
+    pub fn next_step(&mut self) -> Step {
+        let direction = self.direction();
+        let ahead = self.guard.move_ahead(&self.position);
+        let Some(object) = self.map.get(&ahead) else {
+            return Step::Exit;
+        };
+        let entry = if Some(ahead) == self.obstacle {
+            Entry::Block
+        } else if self.broken.contains(&ahead) {
+            Entry::Walk
+        } else {
+            object.entry(direction)
+        };
+
+        match entry {
+            Entry::Walk => Step::Move { to: ahead, direction },
+            Entry::Teleport(_) => Step::Move { to: self.map.portal_exit(ahead).unwrap_or(ahead), direction },
+            Entry::Redirect(forced) => Step::Move { to: ahead, direction: forced },
+            Entry::Block | Entry::Break => {
+                if entry == Entry::Break {
+                    self.broken.push(ahead);
+                }
+                let to = match self.map.get(&self.position) {
+                    Some(standing) if !standing.grips() => direction.reverse(),
+                    _ => self.behaviour.turn(direction),
+                };
+                self.guard = Guard::new(to);
+                Step::Turn { from: direction, to }
+            },
+        }
+    }
+
+    pub fn advance(&mut self, to: Position, direction: Direction) {
+        self.position = to;
+        self.guard = Guard::new(direction);
+    }
+
+    pub fn step(&mut self) -> Step {
+        let step = self.next_step();
+        if let Step::Move { to, direction } = step {
+            self.advance(to, direction);
+        }
+        step
+    }
+
+    // Puts the guard back into an earlier state, forgetting walls broken
+    // since then.
+    pub fn reset(&mut self, position: Position, direction: Direction, broken: usize) {
+        self.advance(position, direction);
+        self.broken.truncate(broken);
+    }
+
+    pub fn behaviour(&self) -> &B {
+        &self.behaviour
+    }
+
+    // How many states a guard without behaviour state can go through before
+    // it repeats one: every tile, eight directions and any number of broken
+    // walls.
+    pub fn state_limit(&self) -> usize {
+        let breakable = self.map.tiles()
+            .filter(|(_, _, object)| Direction::ALL.iter().any(|&heading| object.entry(heading) == Entry::Break))
+            .count();
+        let (rows, cols) = self.map.size();
+        rows * cols * 8 * (breakable + 1)
+    }
+
+    // Every state of the guard in order, and how the walk ended.
+    pub fn walk(mut self) -> (Vec<(Position, Direction)>, WalkEnd) {
+        let mut states = vec![(self.position, self.direction())];
+        // Walls only ever break, so how many have broken is enough to tell
+        // two visits of the same state apart.
+        let mut seen = HashMap::from([((self.position, self.direction(), 0, self.behaviour.state()), 0)]);
+        let limit = self.state_limit();
+
+        loop {
+            let (facing, ahead) = (self.direction(), self.guard.move_ahead(&self.position));
+            if self.step() == Step::Exit {
+                return (states, WalkEnd::Exited);
+            }
+            let straight = self.direction() == facing && self.position == ahead;
+
+            if !straight || !self.behaviour.slides() {
+                let state = (self.position, self.direction(), self.broken.len(), self.behaviour.state());
+                if let Some(&first_seen) = seen.get(&state) {
+                    return (states, WalkEnd::Looped(first_seen));
+                }
+                seen.insert(state, states.len());
+            }
+            states.push((self.position, self.direction()));
+            if seen.len() > limit {
+                return (states, WalkEnd::Undecided);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::TurnRight;
+
+    #[test]
+    fn test_steps() {
+        let map = Map::parse("#..\n.→.\n^A.\n.A.").unwrap();
+        let mut walker = Walker::new(&map, TurnRight);
+
+        assert_eq!(walker.step(), Step::Move { to: (1, 0), direction: Direction::Up });
+        assert_eq!(walker.step(), Step::Turn { from: Direction::Up, to: Direction::Right });
+        assert_eq!(walker.step(), Step::Move { to: (1, 1), direction: Direction::Right });
+        assert_eq!(walker.step(), Step::Move { to: (1, 2), direction: Direction::Right });
+        assert_eq!(walker.step(), Step::Exit);
+
+        let mut walker = Walker::at(&map, (1, 1), Direction::Down, TurnRight);
+        assert_eq!(walker.step(), Step::Move { to: (3, 1), direction: Direction::Down });
+    }
+
+    #[test]
+    fn test_obstacle() {
+        let map = Map::parse(".#\n^.").unwrap();
+
+        let walker = Walker::new(&map, TurnRight).with_obstacle((0, 0)).unwrap();
+        assert_eq!(walker.clone().walk().0, [((1, 0), Direction::Up), ((1, 0), Direction::Right), ((1, 1), Direction::Right)]);
+        assert_eq!(walker.obstacle(), Some((0, 0)));
+        assert_eq!(map.get(&(0, 0)), Some(&Object::Empty));
+
+        assert_eq!(Walker::new(&map, TurnRight).with_obstacle((1, 0)).err(), Some(MapError::OccupiedByGuard((1, 0))));
+        assert_eq!(Walker::new(&map, TurnRight).with_obstacle((0, 2)).err(), Some(MapError::OutOfBounds((0, 2))));
+
+        let map = Map::parse(".A\n^A").unwrap();
+        assert_eq!(Walker::new(&map, TurnRight).with_obstacle((0, 1)).err(), Some(MapError::PortalTile((0, 1))));
+    }
+}